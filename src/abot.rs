@@ -20,20 +20,38 @@
 // SOFTWARE.
 
 use crate::cache::{create_or_await_pool, get_conn, CacheKey, RedisPool};
+use crate::coalescer::{CoalescedBuffer, Coalescer};
 use crate::config::CONFIG;
 use crate::errors::{AbotError, CacheError};
+use crate::http_client;
 use crate::matrix::Matrix;
-use log::error;
+use crate::metrics::Metrics;
+use crate::notifier::Notifier;
+use crate::report::{digest_report, new_members_message, ReportKind};
+use crate::tasks::Tasks;
+use crate::telegram::Telegram;
+use crate::watchdog::Watchdog;
+use crate::webhook::Webhook;
+use chrono::{DateTime, FixedOffset, NaiveTime, Utc};
+use log::{error, info, warn};
+use rand::Rng;
 use redis::aio::Connection;
 use reqwest::Url;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::{result::Result, thread, time};
+use std::convert::TryFrom;
+use std::{result::Result, time};
 
 #[derive(Clone)]
 pub struct Abot {
     matrix: Matrix,
+    telegram: Option<Telegram>,
+    webhook: Option<Webhook>,
     pub cache: RedisPool,
+    pub metrics: Metrics,
+    pub coalescer: Coalescer,
+    pub watchdog: Watchdog,
+    tasks: Tasks,
 }
 
 impl Abot {
@@ -45,9 +63,32 @@ impl Abot {
             Default::default()
         });
 
+        // Initialize telegram client if enabled
+        let telegram = if CONFIG.telegram_enabled {
+            Some(Telegram::new())
+        } else {
+            None
+        };
+
+        // Initialize outbound webhook if defined
+        let webhook = if !CONFIG.outbound_webhook_url.is_empty() {
+            Some(Webhook::new(&CONFIG.outbound_webhook_url))
+        } else {
+            None
+        };
+
         Abot {
             matrix,
+            telegram,
+            webhook,
             cache: create_or_await_pool(CONFIG.clone()),
+            metrics: Metrics::new(),
+            coalescer: Coalescer::default(),
+            watchdog: Watchdog::new(
+                time::Duration::from_secs(CONFIG.alert_freshness_window_secs),
+                time::Instant::now(),
+            ),
+            tasks: Tasks::default(),
         }
     }
 
@@ -56,40 +97,203 @@ impl Abot {
         &self.matrix
     }
 
+    /// Returns the outbound webhook, if defined
+    pub fn webhook(&self) -> Option<&Webhook> {
+        self.webhook.as_ref()
+    }
+
+    /// Returns the registry of background tasks
+    pub fn tasks(&self) -> &Tasks {
+        &self.tasks
+    }
+
+    /// Returns all enabled notification backends
+    pub fn notifiers(&self) -> Vec<&dyn Notifier> {
+        let mut notifiers: Vec<&dyn Notifier> = vec![&self.matrix];
+        if let Some(telegram) = &self.telegram {
+            notifiers.push(telegram);
+        }
+        notifiers
+    }
+
     /// Spawn and restart on error
-    pub fn start() {
-        // Fetch and cache member Ids
-        spawn_and_fetch_members_from_remote_url();
+    pub fn start(&self) {
+        // Fetch and cache member Ids reusing the shared cache pool
+        spawn_and_fetch_members_from_remote_url(
+            &self.tasks,
+            self.cache.clone(),
+            self.matrix.clone(),
+        );
 
         // Authenticate matrix and spawn lazy load commands
-        spawn_and_restart_matrix_lazy_load_on_error();
+        spawn_and_restart_matrix_lazy_load_on_error(&self.tasks);
+
+        // Send the daily digest to the users subscribed
+        spawn_daily_digests(&self.tasks, self.matrix.clone());
+
+        // Warn when the monitor stops pushing alerts
+        spawn_freshness_watchdog(&self.tasks, self.watchdog.clone());
+
+        // Deliver the alerts buffered once their coalescing window is over
+        spawn_coalesced_deliveries(&self.tasks, self.clone());
     }
 }
 
+// spawns a task to deliver the coalesced alerts at the end of their window, the
+// buffers left are flushed on shutdown so that no alert is lost
+fn spawn_coalesced_deliveries(tasks: &Tasks, abot: Abot) {
+    if CONFIG.alert_coalesce_window_secs == 0 {
+        return;
+    }
+    let window = time::Duration::from_secs(CONFIG.alert_coalesce_window_secs);
+    let t = tasks.clone();
+    tasks.spawn(async move {
+        while !t.is_shutdown() {
+            t.sleep(time::Duration::from_secs(1)).await;
+            for buffer in abot.coalescer.take_due(window, time::Instant::now()) {
+                deliver_coalesced(&abot, buffer).await;
+            }
+        }
+        for buffer in abot.coalescer.take_all() {
+            deliver_coalesced(&abot, buffer).await;
+        }
+    });
+}
+
+/// Delivers the alerts buffered for a subscriber and member, a single alert is
+/// delivered as is, otherwise a digest of all alerts is delivered
+async fn deliver_coalesced(abot: &Abot, (subscriber, member_id, alerts): CoalescedBuffer) {
+    let (message, formatted_message) = match alerts.as_slice() {
+        [] => return,
+        [alert] => (alert.message.clone(), alert.formatted_message.clone()),
+        _ => {
+            let digest = digest_report(&member_id, &alerts);
+            (digest.message(), digest.formatted_message())
+        }
+    };
+    for notifier in abot.notifiers() {
+        if let Err(e) = notifier
+            .send_private_message(&subscriber, &message, Some(&formatted_message))
+            .await
+        {
+            abot.metrics.inc_failed();
+            error!("Coalesced alerts not delivered to {}: {}", subscriber, e);
+        }
+    }
+}
+
+// spawns a task to warn when no alert is received from the monitor within the window
+fn spawn_freshness_watchdog(tasks: &Tasks, watchdog: Watchdog) {
+    if !watchdog.is_enabled() {
+        return;
+    }
+    let interval = time::Duration::from_secs(CONFIG.alert_freshness_window_secs.clamp(1, 60));
+    let t = tasks.clone();
+    tasks.spawn(async move {
+        while !t.is_shutdown() {
+            t.sleep(interval).await;
+            let now = time::Instant::now();
+            match watchdog.check(now) {
+                Some(true) => warn!(
+                    "No alerts received from the monitor for {}s, check {}",
+                    watchdog.silence(now).as_secs(),
+                    CONFIG.ibp_monitor_url
+                ),
+                Some(false) => info!("Alerts received from the monitor again"),
+                None => {}
+            }
+        }
+    });
+}
+
+// spawns a task to send the daily digest of the previous day at the configured time
+fn spawn_daily_digests(tasks: &Tasks, matrix: Matrix) {
+    if CONFIG.matrix_disabled {
+        return;
+    }
+    let at = match NaiveTime::parse_from_str(CONFIG.digest_time.trim(), "%H:%M") {
+        Ok(at) => at,
+        Err(e) => {
+            warn!(
+                "Daily digest disabled, invalid time '{}': {}",
+                CONFIG.digest_time, e
+            );
+            return;
+        }
+    };
+    let t = tasks.clone();
+    tasks.spawn(async move {
+        while !t.is_shutdown() {
+            t.sleep(until_next_digest(Utc::now(), at)).await;
+            if t.is_shutdown() {
+                break;
+            }
+            let yesterday = Utc::now().date_naive().pred_opt();
+            if let Some(day) = yesterday {
+                if let Err(e) = matrix.send_daily_digests(day).await {
+                    error!("daily digest error: {}", e);
+                }
+            }
+        }
+    });
+}
+
+/// Returns the time left until the next digest is due at the time of the day (UTC)
+fn until_next_digest(now: DateTime<Utc>, at: NaiveTime) -> time::Duration {
+    let today = now.date_naive().and_time(at).and_utc();
+    let next = if today > now {
+        today
+    } else {
+        today + chrono::Duration::days(1)
+    };
+    (next - now).to_std().unwrap_or_default()
+}
+
 // spawns a task to fetch and cache member ids from remote config file
-fn spawn_and_fetch_members_from_remote_url() {
-    async_std::task::spawn(async {
-        if let Err(e) = try_fetch_members_from_remote_url().await {
-            error!("fetch members error: {}", e);
+fn spawn_and_fetch_members_from_remote_url(tasks: &Tasks, cache: RedisPool, matrix: Matrix) {
+    let t = tasks.clone();
+    tasks.spawn(async move {
+        while !t.is_shutdown() {
+            match try_fetch_members_from_remote_url(&cache).await {
+                Ok(refresh) => {
+                    info!(
+                        "Members refreshed: {} added, {} removed",
+                        refresh.added, refresh.removed
+                    );
+                    if let Err(e) = announce_new_members(&matrix, &refresh).await {
+                        error!("announce new members error: {}", e);
+                    }
+                }
+                Err(e) => error!("fetch members error: {}", e),
+            }
+            // members are only fetched once at startup if no refresh interval is defined
+            if CONFIG.members_refresh_interval_secs == 0 {
+                break;
+            }
+            t.sleep(time::Duration::from_secs(
+                CONFIG.members_refresh_interval_secs,
+            ))
+            .await;
         }
     });
 }
 
 // spawns a task to load and process commands from matrix
-fn spawn_and_restart_matrix_lazy_load_on_error() {
-    async_std::task::spawn(async {
+fn spawn_and_restart_matrix_lazy_load_on_error(tasks: &Tasks) {
+    let t = tasks.clone();
+    tasks.spawn(async move {
         let config = CONFIG.clone();
         if !config.matrix_disabled {
-            loop {
+            while !t.is_shutdown() {
                 let mut m = Matrix::new();
                 if let Err(e) = m.authenticate().await {
                     error!("authenticate error: {}", e);
-                    thread::sleep(time::Duration::from_secs(config.error_interval));
+                    t.sleep(error_interval()).await;
                     continue;
                 }
-                if let Err(e) = m.lazy_load_and_process_commands().await {
+                if let Err(e) = m.lazy_load_and_process_commands(&t).await {
                     error!("lazy_load_and_process_commands error: {}", e);
-                    thread::sleep(time::Duration::from_secs(config.error_interval));
+                    t.sleep(error_interval()).await;
                     continue;
                 }
             }
@@ -97,23 +301,69 @@ fn spawn_and_restart_matrix_lazy_load_on_error() {
     });
 }
 
+/// Returns the interval to wait before restarting after an error, with jitter applied
+fn error_interval() -> time::Duration {
+    jittered(
+        time::Duration::from_secs(CONFIG.error_interval),
+        CONFIG.error_interval_jitter,
+        &mut rand::thread_rng(),
+    )
+}
+
+/// Returns the delay randomly shifted by up to ± percent of its value
+fn jittered<R: Rng>(delay: time::Duration, percent: u32, rng: &mut R) -> time::Duration {
+    if percent == 0 {
+        return delay;
+    }
+    let percent = percent.min(100) as f64 / 100.0;
+    delay.mul_f64(1.0 + rng.gen_range(-percent..=percent))
+}
+
 // MemberId represents the member from which we would like to receive alerts from
 pub type MemberId = String;
 
+/// Normalizes a member id so that it matches the members.json keys regardless of case or spacing
+pub fn normalize_member_id(member_id: &str) -> MemberId {
+    member_id.trim().to_lowercase()
+}
+
 // ServiceId represents the service from which the alert has been raised
 pub type ServiceId = String;
 
+/// Normalizes a service id so that it matches the whitelisted services regardless of case or spacing
+pub fn normalize_service_id(service_id: &str) -> ServiceId {
+    service_id.trim().to_lowercase()
+}
+
 // HealthCheckId represents the raw source of the alert, useful to link to external ibp-monitor
 pub type HealthCheckId = u32;
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum Severity {
+    #[default]
     High,
     Medium,
     Low,
 }
 
+impl Severity {
+    /// Returns the severity level, higher severities have higher levels
+    fn level(&self) -> u8 {
+        match self {
+            Self::High => 3,
+            Self::Medium => 2,
+            Self::Low => 1,
+        }
+    }
+}
+
+impl PartialOrd for Severity {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.level().partial_cmp(&other.level())
+    }
+}
+
 impl std::fmt::Display for Severity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -124,21 +374,9 @@ impl std::fmt::Display for Severity {
     }
 }
 
-impl Default for Severity {
-    fn default() -> Self {
-        Severity::High
-    }
-}
-
 impl From<Severity> for String {
     fn from(severity: Severity) -> Self {
-        match severity {
-            Severity::High => "".to_string(),
-            _ => "".to_string(),
-        }
-        // ErrorResponse {
-        //     errors: vec![error.into()],
-        // }
+        severity.to_string()
     }
 }
 
@@ -156,11 +394,38 @@ impl From<&str> for Severity {
 // MuteTime represented in minutes
 pub type MuteTime = u32;
 
+// Maximum number of recent alerts kept by member
+pub const RECENT_ALERTS_CAPACITY: usize = 50;
+
+// Maximum number of alerts queued by member during maintenance
+pub const MAINTENANCE_QUEUE_CAPACITY: usize = 100;
+
+// Default and maximum number of recent alerts replied by the history command
+pub const HISTORY_DEFAULT_SIZE: usize = 10;
+pub const HISTORY_MAX_SIZE: usize = 25;
+
+/// RecentAlert is a summary of an alert kept in the member recent alerts list
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RecentAlert {
+    pub timestamp: i64,
+    pub code: u32,
+    pub severity: Severity,
+    pub service_id: ServiceId,
+}
+
+/// Returns the number of recent alerts to reply, capped to HISTORY_MAX_SIZE
+pub fn history_size(size: Option<usize>) -> usize {
+    size.unwrap_or(HISTORY_DEFAULT_SIZE)
+        .clamp(1, HISTORY_MAX_SIZE)
+        .min(RECENT_ALERTS_CAPACITY)
+}
+
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum MaintenanceMode {
     On,
     Off,
+    Queue,
 }
 
 impl std::fmt::Display for MaintenanceMode {
@@ -168,6 +433,7 @@ impl std::fmt::Display for MaintenanceMode {
         match self {
             Self::On => write!(f, "on"),
             Self::Off => write!(f, "off"),
+            Self::Queue => write!(f, "queue"),
         }
     }
 }
@@ -177,6 +443,7 @@ impl From<&str> for MaintenanceMode {
         match mode {
             "on" => MaintenanceMode::On,
             "off" => MaintenanceMode::Off,
+            "queue" => MaintenanceMode::Queue,
             _ => MaintenanceMode::Off,
         }
     }
@@ -184,12 +451,7 @@ impl From<&str> for MaintenanceMode {
 
 impl From<String> for MaintenanceMode {
     fn from(mode: String) -> Self {
-        let mode = mode.as_str();
-        match mode {
-            "on" => MaintenanceMode::On,
-            "off" => MaintenanceMode::Off,
-            _ => MaintenanceMode::Off,
-        }
+        MaintenanceMode::from(mode.as_str())
     }
 }
 
@@ -207,10 +469,151 @@ impl redis::FromRedisValue for MaintenanceMode {
     }
 }
 
+/// Defines where alerts are delivered to a subscriber
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Delivery {
+    #[default]
+    Private,
+    Public,
+    Both,
+}
+
+impl Delivery {
+    /// Returns true if alerts should be sent to the user private room
+    pub fn is_private(&self) -> bool {
+        matches!(self, Self::Private | Self::Both)
+    }
+
+    /// Returns true if alerts should be sent to the public room
+    pub fn is_public(&self) -> bool {
+        matches!(self, Self::Public | Self::Both)
+    }
+}
+
+impl std::fmt::Display for Delivery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Private => write!(f, "private"),
+            Self::Public => write!(f, "public"),
+            Self::Both => write!(f, "both"),
+        }
+    }
+}
+
+impl From<&str> for Delivery {
+    fn from(delivery: &str) -> Self {
+        match delivery {
+            "private" => Delivery::Private,
+            "public" => Delivery::Public,
+            "both" => Delivery::Both,
+            _ => Delivery::Private,
+        }
+    }
+}
+
+impl redis::FromRedisValue for Delivery {
+    fn from_redis_value(v: &redis::Value) -> redis::RedisResult<Self> {
+        let delivery = match v {
+            redis::Value::Data(buf) => String::from_utf8_lossy(buf).as_ref().into(),
+            _ => Delivery::default(),
+        };
+
+        redis::RedisResult::Ok(delivery)
+    }
+}
+
+/// Defines a daily time window during which alerts are not delivered to a subscriber,
+/// e.g. 23:00-07:00 +02:00. Windows ending before they start wrap past midnight.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+    offset: FixedOffset,
+}
+
+impl QuietHours {
+    /// Parses a window like 23:00-07:00 and an optional UTC offset like UTC, +02:00 or -05
+    pub fn parse(window: &str, tz: Option<&str>) -> Option<QuietHours> {
+        let (start, end) = window.split_once('-')?;
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?;
+        if start == end {
+            return None;
+        }
+        let offset = match tz.map(str::trim) {
+            None | Some("UTC") | Some("utc") | Some("Z") => FixedOffset::east_opt(0)?,
+            Some(tz) => parse_utc_offset(tz)?,
+        };
+        Some(QuietHours { start, end, offset })
+    }
+
+    /// Returns true if the time falls inside the window, in the window's offset
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let time = now.with_timezone(&self.offset).time();
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Parses an UTC offset like +02:00, +0200, +2 or -05, optionally prefixed by UTC
+fn parse_utc_offset(tz: &str) -> Option<FixedOffset> {
+    let tz = tz.strip_prefix("UTC").unwrap_or(tz);
+    let (sign, hhmm) = match tz.chars().next()? {
+        '+' => (1, &tz[1..]),
+        '-' => (-1, &tz[1..]),
+        _ => return None,
+    };
+    // only ascii digits are accepted, so that splitting by byte index below is safe
+    if !hhmm.chars().all(|c| c.is_ascii_digit() || c == ':') {
+        return None;
+    }
+    let (hours, minutes) = match hhmm.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if hhmm.len() > 2 => hhmm.split_at(hhmm.len() - 2),
+        None => (hhmm, "0"),
+    };
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+impl std::fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{} {}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M"),
+            self.offset
+        )
+    }
+}
+
+impl TryFrom<String> for QuietHours {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (window, tz) = match value.trim().split_once(' ') {
+            Some((window, tz)) => (window, Some(tz)),
+            None => (value.trim(), None),
+        };
+        QuietHours::parse(window, tz).ok_or(format!("invalid quiet hours {}", value))
+    }
+}
+
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 pub enum ReportType {
     Alerts(Option<MemberId>, Option<Severity>, Option<MuteTime>),
     Maintenance(Option<(MemberId, MaintenanceMode)>),
+    MaintenanceNotifications(Option<MemberId>),
 }
 
 impl ReportType {
@@ -243,18 +646,32 @@ impl ReportType {
                         mute_time
                     )
                 } else {
-                    format!("All Alerts from all members")
+                    "All Alerts from all members".to_string()
                 }
             }
-            Self::Maintenance(Some((member_id, mode))) => match mode {
-                MaintenanceMode::On => format!(
-                    "🚧 {} site is under maintenance → alerts are muted 🔇",
-                    member_id
-                ),
-                MaintenanceMode::Off => {
-                    format!("💚 {} site is back online → alerts are on 🔊", member_id)
+            Self::Maintenance(Some((member_id, mode))) => {
+                let prefix = ReportKind::from(mode).prefix();
+                match mode {
+                    MaintenanceMode::On => format!(
+                        "{} {} site is under maintenance → alerts are muted 🔇",
+                        prefix, member_id
+                    ),
+                    MaintenanceMode::Off => format!(
+                        "{} {} site is back online → alerts are on 🔊",
+                        prefix, member_id
+                    ),
+                    MaintenanceMode::Queue => format!(
+                        "{} {} site is under maintenance → alerts are queued until it is back online 📥",
+                        prefix, member_id
+                    ),
                 }
-            },
+            }
+            Self::MaintenanceNotifications(Some(member_id)) => {
+                format!("Maintenance notifications from {}", member_id)
+            }
+            Self::MaintenanceNotifications(None) => {
+                "Maintenance notifications from all members".to_string()
+            }
             _ => unimplemented!(),
         }
     }
@@ -265,6 +682,7 @@ impl std::fmt::Display for ReportType {
         match self {
             Self::Alerts(_option_1, _option_2, _option_3) => write!(f, "Alerts"),
             Self::Maintenance(_option_1) => write!(f, "Maintenance"),
+            Self::MaintenanceNotifications(_option_1) => write!(f, "MaintenanceNotifications"),
         }
     }
 }
@@ -273,36 +691,505 @@ impl std::fmt::Display for ReportType {
 pub struct MembersResponse {
     members: HashMap<MemberId, serde_json::Value>,
 }
+
+/// MembersRefresh summarizes the changes applied to the cached members
+#[derive(Debug, Serialize, PartialEq, Default)]
+pub struct MembersRefresh {
+    pub added: usize,
+    pub removed: usize,
+    // members added by this refresh, the initial load of an empty cache is not reported
+    #[serde(skip)]
+    pub new_members: Vec<MemberId>,
+}
+
+// Number of attempts to fetch the members.json file before giving up
+const MEMBERS_FETCH_ATTEMPTS: u32 = 4;
+
+// Delay (in milliseconds) before the first members.json fetch retry, doubled on every retry
+const MEMBERS_FETCH_RETRY_DELAY_MS: u64 = 500;
+
+/// Fetch member ids from a members.json file, transient failures are retried with backoff
+pub async fn fetch_members_from_url(url: &str) -> Result<Vec<MemberId>, AbotError> {
+    fetch_members_with_retry(
+        url,
+        MEMBERS_FETCH_ATTEMPTS,
+        time::Duration::from_millis(MEMBERS_FETCH_RETRY_DELAY_MS),
+    )
+    .await
+}
+
+async fn fetch_members_with_retry(
+    url: &str,
+    attempts: u32,
+    delay: time::Duration,
+) -> Result<Vec<MemberId>, AbotError> {
+    let url = Url::parse(url)?;
+    let mut attempt = 1;
+    loop {
+        match fetch_members(&url).await {
+            Err(AbotError::ReqwestError(e)) if attempt < attempts && is_transient(&e) => {
+                let backoff = delay * 2_u32.pow(attempt - 1);
+                warn!(
+                    "Members fetch failed: {}, retry {}/{} in {:?}",
+                    e,
+                    attempt,
+                    attempts - 1,
+                    backoff
+                );
+                async_std::task::sleep(backoff).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Returns true for network errors and server side failures, client errors (4xx)
+/// and malformed files are not retried
+fn is_transient(error: &reqwest::Error) -> bool {
+    match error.status() {
+        Some(status) => {
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        None => !error.is_decode(),
+    }
+}
+
+async fn fetch_members(url: &Url) -> Result<Vec<MemberId>, AbotError> {
+    // local files (file:///path/to/members.json) are read directly, e.g. for air-gapped deployments
+    let data = if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|_| AbotError::Other(format!("invalid file path {}", url)))?;
+        serde_json::from_str::<MembersResponse>(&async_std::fs::read_to_string(path).await?)?
+    } else {
+        let response = http_client::build(CONFIG.http_timeout_secs)
+            .get(url.clone())
+            .send()
+            .await?
+            .error_for_status()?;
+        response.json::<MembersResponse>().await?
+    };
+    let mut members: Vec<MemberId> = data
+        .members
+        .keys()
+        .map(|member_id| normalize_member_id(member_id))
+        .collect();
+    members.sort();
+    members.dedup();
+    Ok(members)
+}
+
+/// Returns the members to be added and removed from the cached members
+fn members_diff(current: &[MemberId], fetched: &[MemberId]) -> (Vec<MemberId>, Vec<MemberId>) {
+    let added = fetched
+        .iter()
+        .filter(|member| !current.contains(member))
+        .cloned()
+        .collect();
+    let removed = current
+        .iter()
+        .filter(|member| !fetched.contains(member))
+        .cloned()
+        .collect();
+    (added, removed)
+}
+
 /// Fetch members from ibp-monitor main repo https://raw.githubusercontent.com/ibp-network/config/main/members.json
-pub async fn try_fetch_members_from_remote_url() -> Result<(), AbotError> {
+/// and update the cached members, members no longer defined in the file are removed
+pub async fn try_fetch_members_from_remote_url(
+    cache: &RedisPool,
+) -> Result<MembersRefresh, AbotError> {
     let config = CONFIG.clone();
-    if config.members_json_url.len() == 0 {
+    if config.members_json_url.is_empty() {
         return Err(AbotError::Other(
             "config.members_json_url not specified".to_string(),
         ));
     }
 
-    let url = Url::parse(&*config.members_json_url)?;
-    match reqwest::get(url.to_string()).await {
-        Ok(response) => {
-            match response.json::<MembersResponse>().await {
-                Ok(data) => {
-                    // cache members
-                    let cache = create_or_await_pool(CONFIG.clone());
-                    let mut conn = get_conn(&cache).await?;
-                    for (member, _) in data.members {
-                        redis::cmd("SADD")
-                            .arg(CacheKey::Members)
-                            .arg(member.to_string())
-                            .query_async::<Connection, bool>(&mut conn)
-                            .await
-                            .map_err(CacheError::RedisCMDError)?;
-                    }
-                }
-                Err(e) => return Err(AbotError::ReqwestError(e)),
-            }
+    let fetched = fetch_members_from_url(&config.members_json_url).await?;
+    // an empty file is most likely a broken config, keep the cached members
+    if fetched.is_empty() {
+        return Err(AbotError::Other(format!(
+            "no members defined in {}",
+            config.members_json_url
+        )));
+    }
+
+    let mut conn = get_conn(cache).await?;
+    let current = redis::cmd("SMEMBERS")
+        .arg(CacheKey::Members)
+        .query_async::<Connection, Vec<MemberId>>(&mut conn)
+        .await
+        .map_err(CacheError::RedisCMDError)?;
+
+    let (added, removed) = members_diff(&current, &fetched);
+    // members are added one by one so that members added meanwhile by a concurrent refresh
+    // are not reported twice
+    let mut new_members: Vec<MemberId> = Vec::new();
+    if !added.is_empty() {
+        let mut pipe = redis::pipe();
+        for member_id in added.iter() {
+            pipe.cmd("SADD").arg(CacheKey::Members).arg(member_id);
         }
-        Err(e) => return Err(AbotError::ReqwestError(e)),
+        let results = pipe
+            .query_async::<Connection, Vec<u32>>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+        new_members = newly_added(&added, &results);
+    }
+    if !removed.is_empty() {
+        redis::cmd("SREM")
+            .arg(CacheKey::Members)
+            .arg(&removed)
+            .query_async::<Connection, ()>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+    }
+
+    for member_id in new_members.iter() {
+        info!("New member {} added", member_id);
+    }
+    if current.is_empty() {
+        new_members.clear();
+    }
+
+    Ok(MembersRefresh {
+        added: added.len(),
+        removed: removed.len(),
+        new_members,
+    })
+}
+
+/// Returns the members actually added by SADD, 1 if the member was new and 0 if it
+/// already existed
+fn newly_added(candidates: &[MemberId], results: &[u32]) -> Vec<MemberId> {
+    candidates
+        .iter()
+        .zip(results)
+        .filter(|(_, added)| **added == 1)
+        .map(|(member_id, _)| member_id.to_string())
+        .collect()
+}
+
+/// Announces the members newly added to the members.json file in the public room
+pub async fn announce_new_members(
+    matrix: &Matrix,
+    refresh: &MembersRefresh,
+) -> Result<(), AbotError> {
+    if CONFIG.new_members_announcement && !refresh.new_members.is_empty() {
+        let message = new_members_message(&refresh.new_members);
+        matrix.send_public_message(&message, Some(&message)).await?;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn it_jitters_the_error_interval() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let delay = time::Duration::from_secs(30);
+        for _ in 0..100 {
+            let jittered = jittered(delay, 10, &mut rng);
+            assert!(jittered >= time::Duration::from_secs(27));
+            assert!(jittered <= time::Duration::from_secs(33));
+        }
+        // the same seed yields the same delays
+        assert_eq!(
+            jittered(delay, 10, &mut StdRng::seed_from_u64(7)),
+            jittered(delay, 10, &mut StdRng::seed_from_u64(7))
+        );
+        assert_eq!(jittered(delay, 0, &mut rng), delay);
+    }
+
+    #[test]
+    fn it_schedules_the_next_digest() {
+        let at = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+        let now = |h, m| Utc.with_ymd_and_hms(2023, 6, 1, h, m, 0).unwrap();
+        assert_eq!(
+            until_next_digest(now(7, 30), at),
+            time::Duration::from_secs(30 * 60)
+        );
+        assert_eq!(
+            until_next_digest(now(8, 0), at),
+            time::Duration::from_secs(24 * 3600)
+        );
+        assert_eq!(
+            until_next_digest(now(20, 0), at),
+            time::Duration::from_secs(12 * 3600)
+        );
+    }
+
+    #[test]
+    fn it_names_maintenance_modes_without_the_alert_prefix() {
+        let name = |mode| ReportType::Maintenance(Some(("turboflakes".into(), mode))).name();
+        assert_eq!(
+            name(MaintenanceMode::On),
+            "🚧 turboflakes site is under maintenance → alerts are muted 🔇"
+        );
+        assert_eq!(
+            name(MaintenanceMode::Off),
+            "💚 turboflakes site is back online → alerts are on 🔊"
+        );
+        assert!(name(MaintenanceMode::Queue).starts_with("🚧 turboflakes"));
+    }
+
+    #[test]
+    fn it_names_maintenance_notifications() {
+        assert_eq!(
+            ReportType::MaintenanceNotifications(Some("turboflakes".into())).name(),
+            "Maintenance notifications from turboflakes"
+        );
+        assert_eq!(
+            ReportType::MaintenanceNotifications(None).name(),
+            "Maintenance notifications from all members"
+        );
+    }
+
+    #[test]
+    fn it_reads_the_queue_maintenance_mode_from_cache() {
+        for mode in [
+            MaintenanceMode::On,
+            MaintenanceMode::Off,
+            MaintenanceMode::Queue,
+        ] {
+            let value = redis::Value::Data(mode.to_string().into_bytes());
+            assert_eq!(
+                <MaintenanceMode as redis::FromRedisValue>::from_redis_value(&value).unwrap(),
+                mode
+            );
+        }
+        // members never set under maintenance are not queued
+        assert_eq!(
+            <MaintenanceMode as redis::FromRedisValue>::from_redis_value(&redis::Value::Nil)
+                .unwrap(),
+            MaintenanceMode::Off
+        );
+    }
+
+    #[test]
+    fn it_converts_severity_to_string_and_back() {
+        for severity in [Severity::High, Severity::Medium, Severity::Low] {
+            let s = String::from(severity.clone());
+            assert_eq!(s, severity.to_string());
+            assert_eq!(Severity::from(s.as_str()), severity);
+        }
+    }
+
+    #[test]
+    fn it_routes_delivery_to_private_and_public_rooms() {
+        assert_eq!(Delivery::default(), Delivery::Private);
+        assert!(Delivery::Private.is_private() && !Delivery::Private.is_public());
+        assert!(!Delivery::Public.is_private() && Delivery::Public.is_public());
+        assert!(Delivery::Both.is_private() && Delivery::Both.is_public());
+        assert_eq!(Delivery::from("both"), Delivery::Both);
+        assert_eq!(Delivery::from("unknown"), Delivery::Private);
+    }
+
+    #[test]
+    fn it_caps_history_size() {
+        assert_eq!(history_size(None), HISTORY_DEFAULT_SIZE);
+        assert_eq!(history_size(Some(5)), 5);
+        assert_eq!(history_size(Some(0)), 1);
+        assert_eq!(history_size(Some(1000)), HISTORY_MAX_SIZE);
+    }
+
+    #[test]
+    fn it_orders_severities() {
+        assert!(Severity::High > Severity::Medium);
+        assert!(Severity::Medium > Severity::Low);
+        assert!(Severity::Low < Severity::High);
+        assert!(Severity::Medium >= Severity::Medium);
+    }
+
+    #[test]
+    fn it_normalizes_member_ids() {
+        assert_eq!(normalize_member_id("turboflakes"), "turboflakes");
+        assert_eq!(normalize_member_id("Turboflakes"), "turboflakes");
+        assert_eq!(normalize_member_id(" turboflakes "), "turboflakes");
+        assert_eq!(normalize_member_id("\tTurboFlakes\n"), "turboflakes");
+    }
+
+    #[test]
+    fn it_diffs_cached_and_fetched_members() {
+        let current = vec!["amforc".to_string(), "turboflakes".to_string()];
+        let fetched = vec!["turboflakes".to_string(), "stakeplus".to_string()];
+        let (added, removed) = members_diff(&current, &fetched);
+        assert_eq!(added, vec!["stakeplus".to_string()]);
+        assert_eq!(removed, vec!["amforc".to_string()]);
+    }
+
+    #[test]
+    fn it_detects_new_members_from_the_sadd_result() {
+        let candidates = vec![
+            "stakeplus".to_string(),
+            "turboflakes".to_string(),
+            "amforc".to_string(),
+        ];
+        // turboflakes was added meanwhile by a concurrent refresh
+        assert_eq!(
+            newly_added(&candidates, &[1, 0, 1]),
+            vec!["stakeplus".to_string(), "amforc".to_string()]
+        );
+        assert!(newly_added(&candidates, &[0, 0, 0]).is_empty());
+    }
+
+    #[actix_web::test]
+    async fn it_fetches_members_from_url() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/members.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "members": {
+                    "turboflakes": { "name": "TurboFlakes" },
+                    "Amforc ": { "name": "Amforc" }
+                }
+            })))
+            .mount(&server)
+            .await;
+
+        let members = fetch_members_from_url(&format!("{}/members.json", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(
+            members,
+            vec!["amforc".to_string(), "turboflakes".to_string()]
+        );
+    }
+
+    #[actix_web::test]
+    async fn it_fetches_members_from_a_local_file() {
+        let path = std::env::temp_dir().join(format!("abot.members.{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"members":{"turboflakes":{},"Stake.Plus":{"name":"Stake.plus"}}}"#,
+        )
+        .unwrap();
+
+        let url = Url::from_file_path(&path).unwrap();
+        let members = fetch_members_from_url(url.as_str()).await.unwrap();
+        assert_eq!(
+            members,
+            vec!["stake.plus".to_string(), "turboflakes".to_string()]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(fetch_members_from_url(url.as_str()).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn it_retries_a_failed_members_fetch() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        // the first two requests fail, the mock with higher priority is only matched twice
+        Mock::given(method("GET"))
+            .and(path("/members.json"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .with_priority(1)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/members.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "members": { "turboflakes": {} }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let members = fetch_members_with_retry(
+            &format!("{}/members.json", server.uri()),
+            3,
+            time::Duration::from_millis(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(members, vec!["turboflakes".to_string()]);
+    }
+
+    #[actix_web::test]
+    async fn it_does_not_retry_a_missing_members_file() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/members.json"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let result = fetch_members_with_retry(
+            &format!("{}/members.json", server.uri()),
+            3,
+            time::Duration::from_millis(1),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2023-06-01T{}:00Z", time))
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn it_parses_quiet_hours() {
+        let quiet = QuietHours::parse("23:00-07:00", None).unwrap();
+        assert_eq!(quiet.to_string(), "23:00-07:00 +00:00");
+        let quiet = QuietHours::parse("23:00-07:00", Some("+02:00")).unwrap();
+        assert_eq!(quiet.to_string(), "23:00-07:00 +02:00");
+        assert_eq!(QuietHours::try_from(quiet.to_string()), Ok(quiet));
+        let quiet = QuietHours::parse("22:30-06:00", Some("UTC-0530")).unwrap();
+        assert_eq!(quiet.to_string(), "22:30-06:00 -05:30");
+        assert!(QuietHours::parse("23:00", None).is_none());
+        assert!(QuietHours::parse("25:00-07:00", None).is_none());
+        assert!(QuietHours::parse("07:00-07:00", None).is_none());
+        assert!(QuietHours::parse("23:00-07:00", Some("Europe/Lisbon")).is_none());
+        assert!(QuietHours::parse("23:00-07:00", Some("+1éx")).is_none());
+        assert!(QuietHours::parse("23:00-07:00", Some("+é0")).is_none());
+    }
+
+    #[test]
+    fn it_checks_time_inside_quiet_hours() {
+        let quiet = QuietHours::parse("09:00-17:00", None).unwrap();
+        assert!(quiet.contains(at("09:00")));
+        assert!(quiet.contains(at("16:59")));
+        assert!(!quiet.contains(at("17:00")));
+        assert!(!quiet.contains(at("08:59")));
+    }
+
+    #[test]
+    fn it_checks_quiet_hours_wrapping_past_midnight() {
+        let quiet = QuietHours::parse("23:00-07:00", None).unwrap();
+        assert!(quiet.contains(at("23:30")));
+        assert!(quiet.contains(at("00:00")));
+        assert!(quiet.contains(at("06:59")));
+        assert!(!quiet.contains(at("07:00")));
+        assert!(!quiet.contains(at("12:00")));
+        assert!(!quiet.contains(at("22:59")));
+    }
+
+    #[test]
+    fn it_checks_quiet_hours_in_the_given_offset() {
+        // 23:00-07:00 at +02:00 is 21:00-05:00 UTC
+        let quiet = QuietHours::parse("23:00-07:00", Some("+02:00")).unwrap();
+        assert!(quiet.contains(at("21:30")));
+        assert!(!quiet.contains(at("05:30")));
+        assert!(!quiet.contains(at("20:59")));
+    }
+}
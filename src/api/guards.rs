@@ -21,6 +21,7 @@
 
 use crate::config::CONFIG;
 use actix_web::guard::{Guard, GuardContext};
+use actix_web::http::header::HeaderMap;
 use log::warn;
 
 pub struct ApiKeyGuard;
@@ -28,19 +29,63 @@ pub struct ApiKeyGuard;
 impl Guard for ApiKeyGuard {
     fn check(&self, ctx: &GuardContext<'_>) -> bool {
         let config = CONFIG.clone();
+        has_valid_api_key(
+            ctx.head().headers(),
+            &config.api_key_header,
+            &config.api_keys,
+        )
+    }
+}
+
+/// Returns true if the header named header_name carries one of the api keys
+fn has_valid_api_key(headers: &HeaderMap, header_name: &str, api_keys: &[String]) -> bool {
+    let opt = headers.get(header_name.trim());
+
+    if !opt.is_some_and(|hv| api_keys.iter().any(|ak| ak.as_bytes() == hv.as_bytes())) {
+        warn!("Invalid API-Key: {:?}", opt);
+        return false;
+    }
+
+    true
+}
 
-        let opt = ctx.head().headers().get("X-API-KEY");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
 
-        if !opt.map_or(false, |hv| {
-            config
-                .api_keys
-                .iter()
-                .any(|ak| ak.as_bytes() == hv.as_bytes())
-        }) {
-            warn!("Invalid API-Key: {:?}", opt);
-            return false;
-        }
+    fn headers(name: &str, value: &str) -> HeaderMap {
+        TestRequest::default()
+            .insert_header((name, value))
+            .to_http_request()
+            .headers()
+            .clone()
+    }
 
-        true
+    #[test]
+    fn it_reads_the_api_key_from_the_configured_header() {
+        let api_keys = vec!["secret".to_string()];
+        // default header
+        assert!(has_valid_api_key(
+            &headers("X-API-KEY", "secret"),
+            "X-API-KEY",
+            &api_keys
+        ));
+        assert!(!has_valid_api_key(
+            &headers("X-API-KEY", "wrong"),
+            "X-API-KEY",
+            &api_keys
+        ));
+        // custom header, matched ignoring case
+        assert!(has_valid_api_key(
+            &headers("X-Gateway-Token", "secret"),
+            "x-gateway-token",
+            &api_keys
+        ));
+        assert!(!has_valid_api_key(
+            &headers("X-API-KEY", "secret"),
+            "X-Gateway-Token",
+            &api_keys
+        ));
     }
 }
@@ -19,22 +19,35 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use crate::abot::{HealthCheckId, MaintenanceMode, MemberId, ServiceId, Severity};
+use crate::abot::{
+    normalize_member_id, normalize_service_id, Delivery, HealthCheckId, MaintenanceMode, MemberId,
+    QuietHours, RecentAlert, ServiceId, Severity, MAINTENANCE_QUEUE_CAPACITY,
+    RECENT_ALERTS_CAPACITY,
+};
 use crate::api::helpers::respond_json;
-use crate::cache::{get_conn, CacheKey};
-use crate::matrix::UserID;
-use std::collections::HashMap;
-// use crate::config::CONFIG;
-use crate::errors::{ApiError, CacheError};
+use crate::cache::{get_conn, CacheKey, RedisPool};
+use crate::coalescer::CoalescedAlert;
+use crate::config::{AlertDedupKey, CONFIG};
+use crate::errors::{AbotError, ApiError, CacheError};
+use crate::matrix::{EventID, RoomID, UserID};
+use crate::metrics::{Metrics, SkipReason};
+use crate::notifier::Notifier;
 use crate::report::{RawAlert, Report};
 use crate::Abot;
-use actix_web::{web, web::Json};
-use chrono::Utc;
+use actix_web::{web, web::Json, HttpRequest};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use log::{error, info, warn};
 use redis::aio::Connection;
 use serde::{Deserialize, Serialize};
 use serde_json::value::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::future::Future;
+use std::time::Instant;
 
-const WHITELIST_SERVICES: [&'static str; 12] = [
+const WHITELIST_SERVICES: [&str; 12] = [
     "polkadot-rpc",
     "kusama-rpc",
     "westend-rpc",
@@ -49,16 +62,38 @@ const WHITELIST_SERVICES: [&'static str; 12] = [
     "encointer-kusama-rpc",
 ];
 
-#[derive(Debug, Serialize)]
+// Maximum number of health checks accepted in a single alert
+pub const MAX_HEALTH_CHECKS: usize = 64;
+
+// Header carrying the hex encoded HMAC-SHA256 of the alert body
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+
+// Header identifying an alert so that monitor retries are only delivered once
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+// Time (in seconds) an idempotency key is remembered
+const IDEMPOTENCY_KEY_TTL: u32 = 600;
+
+// Maximum length accepted for an idempotency key
+pub const IDEMPOTENCY_KEY_MAX_LEN: usize = 255;
+
+#[derive(Debug, Serialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Status {
     Delivered,
-    _Skipped,
+    Skipped,
+    // the user did not accept the private room invite yet
+    Pending,
+    // the user left the private room and has been invited again
+    Reinvited,
+    // every backend failed to deliver the alert
+    Failed,
 }
 
 #[derive(Debug, Serialize)]
 pub struct Response {
-    data: Vec<(UserID, Status)>,
+    // subscriber, delivery status and the id of the message sent, if delivered
+    data: Vec<(UserID, Status, Option<EventID>)>,
 }
 
 // #[allow(dead_code)]
@@ -96,18 +131,155 @@ pub struct Response {
 pub struct Alert {
     code: u32,
     severity: Severity,
+    // the fields below are informative, so alerts missing them are still delivered
+    #[serde(default)]
     message: String,
     member_id: MemberId,
     service_id: ServiceId,
+    #[serde(default)]
     health_check_id: HealthCheckId,
+    #[serde(default)]
     health_checks: Vec<Value>,
 }
 
+impl Alert {
+    /// Rejects malformed alerts before any processing takes place
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.code == 0 {
+            return Err(ApiError::BadRequest("code must be defined".into()));
+        }
+        if self.member_id.trim().is_empty() {
+            return Err(ApiError::BadRequest("member_id must not be empty".into()));
+        }
+        if self.service_id.trim().is_empty() {
+            return Err(ApiError::BadRequest("service_id must not be empty".into()));
+        }
+        if self.health_checks.len() > MAX_HEALTH_CHECKS {
+            return Err(ApiError::BadRequest(format!(
+                "health_checks must not exceed {} entries",
+                MAX_HEALTH_CHECKS
+            )));
+        }
+        Ok(())
+    }
+}
+
 /// Handler to receive new alerts from monitor
 pub async fn post_alert(
-    new_alert: web::Json<Alert>,
+    req: HttpRequest,
+    payload: web::Payload,
     abot: web::Data<Abot>,
 ) -> Result<Json<Response>, ApiError> {
+    // the raw body is needed to verify the signature before deserializing the alert
+    let body = read_body(payload, CONFIG.max_alert_body_bytes).await?;
+    if !CONFIG.alert_hmac_secret.is_empty() {
+        let signature = req
+            .headers()
+            .get(SIGNATURE_HEADER)
+            .and_then(|hv| hv.to_str().ok());
+        verify_signature(&CONFIG.alert_hmac_secret, &body, signature)?;
+    }
+
+    let mut new_alert: Alert =
+        serde_json::from_slice(&body).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    new_alert.validate()?;
+    // member ids are cached normalized, so the alert must match them before any lookup
+    new_alert.member_id = normalize_member_id(&new_alert.member_id);
+    new_alert.service_id = normalize_service_id(&new_alert.service_id);
+    abot.metrics.inc_received();
+    abot.watchdog.record(Instant::now());
+
+    // skip alerts already received with the same idempotency key (e.g. monitor retries)
+    let response = match idempotency_key(&req)? {
+        Some(key) => {
+            let cache_key = CacheKey::IdempotencyKey(key.to_string());
+            let response = once_per_key(
+                claim_idempotency_key(&abot.cache, cache_key.clone()),
+                process_alert(&abot, new_alert),
+                || release_key(&abot.cache, cache_key.clone()),
+            )
+            .await?;
+            if response.is_none() {
+                warn!("Duplicate alert with idempotency key {} skipped", key);
+            }
+            response.unwrap_or(Response { data: vec![] })
+        }
+        None => process_alert(&abot, new_alert).await?,
+    };
+    respond_json(response)
+}
+
+/// Runs the processing of an alert unless its idempotency key has already been claimed, and
+/// returns None for duplicates. The key is released if the processing fails, so that the
+/// retry of the monitor is processed again instead of being dropped as a duplicate.
+async fn once_per_key<T, E, Fut>(
+    claim: impl Future<Output = Result<bool, E>>,
+    process: impl Future<Output = Result<T, E>>,
+    release: impl FnOnce() -> Fut,
+) -> Result<Option<T>, E>
+where
+    Fut: Future<Output = Result<(), E>>,
+{
+    if !claim.await? {
+        return Ok(None);
+    }
+    match process.await {
+        Ok(processed) => Ok(Some(processed)),
+        Err(e) => {
+            release().await?;
+            Err(e)
+        }
+    }
+}
+
+/// Claims the idempotency key, returns false if it has already been claimed
+async fn claim_idempotency_key(cache: &RedisPool, key: CacheKey) -> Result<bool, ApiError> {
+    let mut conn = get_conn(cache).await?;
+    let claimed = redis::cmd("SET")
+        .arg(key)
+        .arg(Utc::now().timestamp())
+        .arg("NX")
+        .arg("EX")
+        .arg(IDEMPOTENCY_KEY_TTL)
+        .query_async::<Connection, Option<String>>(&mut conn)
+        .await
+        .map_err(CacheError::RedisCMDError)?
+        .is_some();
+    Ok(claimed)
+}
+
+/// Claims the callout of an alert for the mute window, returns false if it has already been
+/// called out within the window. Alerts are always called out when the mute time is zero.
+async fn claim_callout(cache: &RedisPool, key: CacheKey, window: u64) -> Result<bool, ApiError> {
+    if window == 0 {
+        return Ok(true);
+    }
+    let mut conn = get_conn(cache).await?;
+    let claimed = redis::cmd("SET")
+        .arg(key)
+        .arg(Utc::now().timestamp())
+        .arg("NX")
+        .arg("EX")
+        .arg(window)
+        .query_async::<Connection, Option<String>>(&mut conn)
+        .await
+        .map_err(CacheError::RedisCMDError)?
+        .is_some();
+    Ok(claimed)
+}
+
+async fn release_key(cache: &RedisPool, key: CacheKey) -> Result<(), ApiError> {
+    let mut conn = get_conn(cache).await?;
+    redis::cmd("DEL")
+        .arg(key)
+        .query_async::<Connection, ()>(&mut conn)
+        .await
+        .map_err(CacheError::RedisCMDError)?;
+    Ok(())
+}
+
+/// Delivers the alert to its subscribers
+async fn process_alert(abot: &Abot, mut new_alert: Alert) -> Result<Response, ApiError> {
     let mut conn = get_conn(&abot.cache).await?;
 
     // get maintenance status for the member in the alert
@@ -118,9 +290,84 @@ pub async fn post_alert(
         .await
         .map_err(CacheError::RedisCMDError)?;
 
-    // if maintenance is active for the member skip alerts
-    if maintenance_mode == MaintenanceMode::On {
-        return respond_json(Response { data: vec![] });
+    // if maintenance is active for the member skip alerts, queued alerts are delivered
+    // as a digest when the maintenance ends
+    match maintenance_mode {
+        MaintenanceMode::On => {
+            abot.metrics.inc_skipped(SkipReason::Maintenance);
+            return Ok(Response { data: vec![] });
+        }
+        MaintenanceMode::Queue => {
+            let queued_alert = serde_json::to_string(&RecentAlert {
+                timestamp: Utc::now().timestamp(),
+                code: new_alert.code,
+                severity: new_alert.severity.clone(),
+                service_id: new_alert.service_id.to_string(),
+            })?;
+            redis::pipe()
+                .cmd("RPUSH")
+                .arg(CacheKey::MaintenanceQueue(new_alert.member_id.to_string()))
+                .arg(queued_alert)
+                .ignore()
+                .cmd("LTRIM")
+                .arg(CacheKey::MaintenanceQueue(new_alert.member_id.to_string()))
+                .arg(-(MAINTENANCE_QUEUE_CAPACITY as i64))
+                .arg(-1)
+                .ignore()
+                .query_async::<Connection, ()>(&mut conn)
+                .await
+                .map_err(CacheError::RedisCMDError)?;
+            abot.metrics.inc_skipped(SkipReason::Maintenance);
+            return Ok(Response { data: vec![] });
+        }
+        MaintenanceMode::Off => (),
+    }
+
+    // escalate the severity of alerts that keep firing within the escalation window
+    let mut escalated_from: Option<Severity> = None;
+    if CONFIG.alert_escalation_window_secs > 0 {
+        let key = CacheKey::AlertOccurrences(new_alert.member_id.to_string(), new_alert.code);
+        let previous = redis::cmd("HMGET")
+            .arg(key.clone())
+            .arg("start".to_string())
+            .arg("count".to_string())
+            .query_async::<Connection, (Option<i64>, Option<u32>)>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+        let (start, count) = next_occurrence(
+            previous.0.zip(previous.1),
+            Utc::now().timestamp(),
+            CONFIG.alert_escalation_window_secs as i64,
+        );
+        redis::pipe()
+            .cmd("HSET")
+            .arg(key.clone())
+            .arg("start".to_string())
+            .arg(start)
+            .arg("count".to_string())
+            .arg(count)
+            .ignore()
+            .cmd("EXPIREAT")
+            .arg(key)
+            .arg(start + CONFIG.alert_escalation_window_secs as i64)
+            .ignore()
+            .query_async::<Connection, ()>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+
+        let severity = escalated_severity(
+            &new_alert.severity,
+            count,
+            CONFIG.alert_escalation_medium,
+            CONFIG.alert_escalation_high,
+        );
+        if severity != new_alert.severity {
+            info!(
+                "Alert {} from {} escalated from {} to {} after {} occurrences",
+                new_alert.code, new_alert.member_id, new_alert.severity, severity, count
+            );
+            escalated_from = Some(std::mem::replace(&mut new_alert.severity, severity));
+        }
     }
 
     // get all subscribers for the type of alert received by member and severity
@@ -133,14 +380,148 @@ pub async fn post_alert(
         .await
         .map_err(CacheError::RedisCMDError)?;
 
-    let mut resp_data: Vec<(UserID, Status)> = Vec::new();
+    // alerts from services not whitelisted are never delivered, make the drop visible
+    if !is_whitelisted(&new_alert.service_id) {
+        let notice = not_whitelisted_message(&new_alert, subscribers.len());
+        warn!("{}", notice);
+        if CONFIG.not_whitelisted_notice {
+            notify_admins_once(abot, &mut conn, &new_alert.service_id, &notice).await?;
+        }
+    }
+
+    // call out alerts from all members with the configured severities, once per mute window
+    if CONFIG.is_callout_severity(&new_alert.severity) && is_whitelisted(&new_alert.service_id) {
+        let callout_key = callout_key(&new_alert);
+        let report = Report::from(RawAlert {
+            code: new_alert.code,
+            member_id: new_alert.member_id.to_owned(),
+            service_id: new_alert.service_id.to_owned(),
+            health_check_id: new_alert.health_check_id.to_owned(),
+            severity: new_alert.severity.clone(),
+            message: new_alert.message.to_owned(),
+            data: serde_json::to_string(&new_alert.health_checks)?,
+            escalated_from: escalated_from.clone(),
+        });
+        let window = u64::from(CONFIG.mute_time_for(&new_alert.severity)) * 60;
+        let called_out = once_per_key(
+            claim_callout(&abot.cache, callout_key.clone(), window),
+            async {
+                abot.matrix()
+                    .send_callout_message(&report.message(), Some(&report.formatted_message()))
+                    .await
+                    .map_err(ApiError::from)
+            },
+            || release_key(&abot.cache, callout_key.clone()),
+        )
+        .await;
+        match called_out {
+            Ok(Some(_)) => (),
+            Ok(None) => info!(
+                "Alert {} from {} already called out",
+                new_alert.code, new_alert.member_id
+            ),
+            Err(e) => warn!("Callout failed for alert {}: {}", new_alert.code, e),
+        }
+    }
+
+    let mut resp_data: Vec<(UserID, Status, Option<EventID>)> = Vec::new();
+    let mut public = PublicDelivery::default();
 
     for subscriber in subscribers {
-        // get last time the same alert code:service as been sent
-        let key = format!(
-            "{}:{}",
-            new_alert.code.to_string(),
-            new_alert.service_id.to_string()
+        // verify if the subscriber has paused all alerts
+        let paused = redis::cmd("EXISTS")
+            .arg(CacheKey::UserPaused(subscriber.to_string()))
+            .query_async::<Connection, bool>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+
+        // skip alerts from chains other than the one the subscription is filtered by
+        let chain = redis::cmd("HGET")
+            .arg(CacheKey::SubscriberConfig(
+                subscriber.to_string(),
+                new_alert.member_id.to_string(),
+                new_alert.severity.clone(),
+            ))
+            .arg("chain".to_string())
+            .query_async::<Connection, Option<String>>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+
+        if !matches_chain(
+            chain.as_deref(),
+            &new_alert.health_checks,
+            &new_alert.service_id,
+        ) {
+            abot.metrics.inc_skipped(SkipReason::Chain);
+            resp_data.push((subscriber, Status::Skipped, None));
+            continue;
+        }
+
+        // skip alert codes snoozed by the user
+        let snoozed_until = redis::cmd("HGET")
+            .arg(CacheKey::SnoozedCodes(subscriber.to_string()))
+            .arg(new_alert.code.to_string())
+            .query_async::<Connection, Option<i64>>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+
+        if !paused && is_snoozed(snoozed_until, Utc::now().timestamp()) {
+            abot.metrics.inc_skipped(SkipReason::Snoozed);
+            resp_data.push((subscriber, Status::Skipped, None));
+            continue;
+        }
+
+        // skip alerts during the quiet hours defined by the user
+        let quiet_hours = redis::cmd("HGET")
+            .arg(CacheKey::SubscriberConfig(
+                subscriber.to_string(),
+                new_alert.member_id.to_string(),
+                new_alert.severity.clone(),
+            ))
+            .arg("quiet".to_string())
+            .query_async::<Connection, Option<String>>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?
+            .and_then(|quiet| QuietHours::try_from(quiet).ok());
+
+        if !paused && is_quiet(quiet_hours.as_ref(), Utc::now()) {
+            abot.metrics.inc_skipped(SkipReason::Quiet);
+            resp_data.push((subscriber, Status::Skipped, None));
+            continue;
+        }
+
+        // skip alerts below the severity threshold defined by the user
+        let threshold = redis::cmd("HMGET")
+            .arg(CacheKey::SubscriberConfig(
+                subscriber.to_string(),
+                new_alert.member_id.to_string(),
+                new_alert.severity.clone(),
+            ))
+            .arg("threshold".to_string())
+            .arg("threshold_expiry".to_string())
+            .query_async::<Connection, (Option<String>, Option<i64>)>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+
+        if !paused
+            && below_threshold(
+                &new_alert.severity,
+                threshold.0.as_deref().map(Severity::from),
+                threshold.1.unwrap_or(0),
+                Utc::now().timestamp(),
+            )
+        {
+            abot.metrics.inc_skipped(SkipReason::Threshold);
+            resp_data.push((subscriber, Status::Skipped, None));
+            continue;
+        }
+
+        // get last time the same alert code:service (or content) as been sent
+        let key = last_alert_key(
+            &CONFIG.alert_dedup_key,
+            new_alert.code,
+            &new_alert.service_id,
+            &new_alert.message,
         );
         let exists = redis::cmd("HEXISTS")
             .arg(CacheKey::LastAlerts(
@@ -178,11 +559,22 @@ pub async fn post_alert(
             .await
             .map_err(CacheError::RedisCMDError)?;
 
+        // get delivery defined by the user, alerts are delivered privately by default
+        let delivery = redis::cmd("HGET")
+            .arg(CacheKey::SubscriberConfig(
+                subscriber.to_string(),
+                new_alert.member_id.to_string(),
+                new_alert.severity.clone(),
+            ))
+            .arg("delivery".to_string())
+            .query_async::<Connection, Delivery>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+
         // send alert and update last_alert timestamp
         let now = Utc::now();
-        if now.timestamp() > last_time_sent + (mute_time * 60)
-            && WHITELIST_SERVICES.contains(&&new_alert.service_id[..])
-        {
+        let status = delivery_status(paused, last_time_sent, mute_time, now.timestamp());
+        if status == Some(Status::Delivered) && is_whitelisted(&new_alert.service_id) {
             let record_serialized = serde_json::to_string(&new_alert.health_checks)?;
 
             let report = Report::from(RawAlert {
@@ -193,16 +585,143 @@ pub async fn post_alert(
                 severity: new_alert.severity.clone(),
                 message: new_alert.message.to_owned(),
                 data: record_serialized,
+                escalated_from: escalated_from.clone(),
             });
 
-            let _ = &abot
-                .matrix()
-                .send_private_message(
+            // users that left their private room are invited again so that alerts resume
+            // a failed membership check must not hold the alert back
+            let reinvited = delivery.is_private()
+                && abot
+                    .matrix()
+                    .reinvite_to_private_room(&subscriber)
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!(
+                            "Private room membership of {} not checked: {}",
+                            subscriber, e
+                        );
+                        false
+                    });
+
+            // private alerts are held while the user has not accepted the private room invite
+            let pending = delivery.is_private()
+                && abot
+                    .matrix()
+                    .is_private_room_invite_pending(&subscriber)
+                    .await?;
+            if pending && !delivery.is_public() {
+                warn!(
+                    "Alert {} pending for {}: private room invite not accepted",
+                    new_alert.code, subscriber
+                );
+                abot.metrics.inc_skipped(SkipReason::Pending);
+                resp_data.push((subscriber, Status::Pending, None));
+                continue;
+            }
+
+            // private alerts are buffered and delivered as a digest at the end of the window
+            let coalesce =
+                CONFIG.alert_coalesce_window_secs > 0 && delivery.is_private() && !pending;
+            if coalesce {
+                abot.coalescer.push(
                     &subscriber,
-                    &report.message(),
-                    Some(&report.formatted_message()),
+                    &new_alert.member_id,
+                    CoalescedAlert {
+                        code: new_alert.code,
+                        severity: new_alert.severity.clone(),
+                        service_id: new_alert.service_id.to_string(),
+                        message: report.message(),
+                        formatted_message: report.formatted_message(),
+                    },
+                );
+            }
+
+            // repeated private alerts are sent as replies in the thread of the first alert
+            let private = delivery.is_private() && !pending && !coalesce;
+
+            // private alerts are sent to the room the subscriber routed the member to, if any
+            let route = if private {
+                redis::cmd("HGET")
+                    .arg(CacheKey::SubscriberRoutes(subscriber.to_string()))
+                    .arg(new_alert.member_id.to_string())
+                    .query_async::<Connection, Option<RoomID>>(&mut conn)
+                    .await
+                    .map_err(CacheError::RedisCMDError)?
+            } else {
+                None
+            };
+            let threaded = private && route.is_none() && CONFIG.alert_thread_window_secs > 0;
+
+            let thread_key = CacheKey::AlertThread(
+                subscriber.to_string(),
+                new_alert.member_id.to_string(),
+                new_alert.code,
+            );
+            let thread_root = if threaded {
+                redis::cmd("GET")
+                    .arg(thread_key.clone())
+                    .query_async::<Connection, Option<EventID>>(&mut conn)
+                    .await
+                    .map_err(CacheError::RedisCMDError)?
+            } else {
+                None
+            };
+
+            // a failing backend does not prevent the others from delivering the alert, the
+            // subscriber is only failed, and retried with the next alert, if all of them failed
+            let mut event_id: Option<EventID> = None;
+            if private {
+                match send_private_report(
+                    &abot.notifiers(),
+                    &subscriber,
+                    route.as_deref(),
+                    thread_root.as_deref(),
+                    &report,
+                    &abot.metrics,
                 )
-                .await?;
+                .await
+                {
+                    Ok(id) => event_id = id,
+                    Err(e) => {
+                        error!(
+                            "Alert {} not delivered to {}: {}",
+                            new_alert.code, subscriber, e
+                        );
+                        resp_data.push((subscriber, Status::Failed, None));
+                        continue;
+                    }
+                }
+            }
+
+            // the public message is sent once, after all subscribers are processed
+            if delivery.is_public() {
+                public.request(resp_data.len(), &report);
+            }
+
+            // remember the first alert as the thread root, or extend the thread window
+            if threaded {
+                match (&thread_root, &event_id) {
+                    (Some(_), _) => {
+                        redis::cmd("EXPIRE")
+                            .arg(thread_key)
+                            .arg(CONFIG.alert_thread_window_secs)
+                            .query_async::<Connection, bool>(&mut conn)
+                            .await
+                            .map_err(CacheError::RedisCMDError)?;
+                    }
+                    (None, Some(root)) => {
+                        redis::cmd("SET")
+                            .arg(thread_key)
+                            .arg(root)
+                            .arg("EX")
+                            .arg(CONFIG.alert_thread_window_secs)
+                            .query_async::<Connection, ()>(&mut conn)
+                            .await
+                            .map_err(CacheError::RedisCMDError)?;
+                    }
+                    (None, None) => {}
+                }
+            }
 
             //
             let data = HashMap::from([
@@ -215,15 +734,80 @@ pub async fn post_alert(
                     new_alert.member_id.to_string(),
                 ))
                 .arg(data)
-                .query_async::<Connection, _>(&mut conn)
+                .query_async::<Connection, ()>(&mut conn)
                 .await
                 .map_err(CacheError::RedisCMDError)?;
 
-            resp_data.push((subscriber, Status::Delivered));
+            abot.metrics.inc_delivered();
+            let status = if reinvited {
+                Status::Reinvited
+            } else {
+                Status::Delivered
+            };
+            resp_data.push((subscriber, status, event_id));
+        } else if status == Some(Status::Delivered) {
+            abot.metrics.inc_skipped(SkipReason::NotWhitelisted);
+        } else if status == Some(Status::Skipped) {
+            abot.metrics.inc_skipped(SkipReason::Paused);
+            resp_data.push((subscriber, Status::Skipped, None));
+        } else if status.is_none() {
+            abot.metrics.inc_skipped(SkipReason::Mute);
         }
     }
 
+    if let Err(e) = public
+        .send(&abot.notifiers(), &mut resp_data, &abot.metrics)
+        .await
+    {
+        error!("Alert {} not posted publicly: {}", new_alert.code, e);
+    }
+
+    // mirror the alert to the outbound webhook without holding the response
+    if let Some(webhook) = abot.webhook() {
+        let webhook = webhook.clone();
+        let raw_alert = RawAlert {
+            code: new_alert.code,
+            member_id: new_alert.member_id.to_owned(),
+            service_id: new_alert.service_id.to_owned(),
+            health_check_id: new_alert.health_check_id.to_owned(),
+            severity: new_alert.severity.clone(),
+            message: new_alert.message.to_owned(),
+            data: serde_json::to_string(&new_alert.health_checks)?,
+            escalated_from: escalated_from.clone(),
+        };
+        async_std::task::spawn(async move {
+            if let Err(e) = webhook.send_alert(&raw_alert).await {
+                error!("{}", e);
+            }
+        });
+    }
+
     let now = Utc::now();
+    // keep a capped list of the most recent alerts by member
+    let recent_alert = serde_json::to_string(&RecentAlert {
+        timestamp: now.timestamp(),
+        code: new_alert.code,
+        severity: new_alert.severity.clone(),
+        service_id: new_alert.service_id.to_string(),
+    })?;
+    redis::pipe()
+        .cmd("LPUSH")
+        .arg(CacheKey::RecentAlerts(new_alert.member_id.to_string()))
+        .arg(recent_alert)
+        .ignore()
+        .cmd("LTRIM")
+        .arg(CacheKey::RecentAlerts(new_alert.member_id.to_string()))
+        .arg(0)
+        .arg(RECENT_ALERTS_CAPACITY - 1)
+        .ignore()
+        .cmd("SET")
+        .arg(CacheKey::LastAlertTimestamp)
+        .arg(now.timestamp())
+        .ignore()
+        .query_async::<Connection, ()>(&mut conn)
+        .await
+        .map_err(CacheError::RedisCMDError)?;
+
     // increment alert code counter
     redis::cmd("HINCRBY")
         .arg(CacheKey::StatsByCode(
@@ -232,7 +816,7 @@ pub async fn post_alert(
         ))
         .arg(new_alert.code.to_string())
         .arg(1)
-        .query_async::<Connection, _>(&mut conn)
+        .query_async::<Connection, ()>(&mut conn)
         .await
         .map_err(CacheError::RedisCMDError)?;
 
@@ -244,7 +828,7 @@ pub async fn post_alert(
         ))
         .arg(new_alert.severity.to_string())
         .arg(1)
-        .query_async::<Connection, _>(&mut conn)
+        .query_async::<Connection, ()>(&mut conn)
         .await
         .map_err(CacheError::RedisCMDError)?;
 
@@ -256,9 +840,1028 @@ pub async fn post_alert(
         ))
         .arg(new_alert.service_id.to_string())
         .arg(1)
-        .query_async::<Connection, _>(&mut conn)
+        .query_async::<Connection, ()>(&mut conn)
         .await
         .map_err(CacheError::RedisCMDError)?;
 
-    respond_json(Response { data: resp_data })
+    Ok(Response { data: resp_data })
+}
+
+/// Collects the subscribers asking for public delivery of an alert, so that the alert is
+/// posted once to the public channels whatever the number of subscribers
+#[derive(Default)]
+struct PublicDelivery {
+    // plain and formatted message of the alert
+    message: Option<(String, String)>,
+    // index in the response of the subscribers asking for public delivery
+    subscribers: Vec<usize>,
+}
+
+impl PublicDelivery {
+    fn request(&mut self, index: usize, report: &Report) {
+        if self.message.is_none() {
+            self.message = Some((report.message(), report.formatted_message()));
+        }
+        self.subscribers.push(index);
+    }
+
+    /// Sends the public message, if requested, through every notifier and sets the id of the
+    /// message on the subscribers without one. Subscribers only delivered publicly are failed
+    /// if no notifier sent the message.
+    async fn send(
+        self,
+        notifiers: &[&dyn Notifier],
+        resp_data: &mut [(UserID, Status, Option<EventID>)],
+        metrics: &Metrics,
+    ) -> Result<(), AbotError> {
+        let (message, formatted_message) = match self.message {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+        // keep the id of the first message sent (matrix is always the first notifier)
+        let mut event_id: Option<EventID> = None;
+        let mut error: Option<AbotError> = None;
+        let mut sent = false;
+        for notifier in notifiers {
+            match notifier
+                .send_public_message(&message, Some(&formatted_message))
+                .await
+            {
+                // a backend without a public channel, e.g. telegram without chat id, sends nothing
+                Ok(Some(id)) => {
+                    sent = true;
+                    event_id = event_id.or(Some(id));
+                }
+                Ok(None) => (),
+                Err(e) => {
+                    warn!("Public message not sent: {}", e);
+                    metrics.inc_failed();
+                    error = Some(e);
+                }
+            }
+        }
+        for index in self.subscribers {
+            if let Some((_, status, id)) = resp_data.get_mut(index) {
+                if id.is_none() {
+                    *id = event_id.clone();
+                    if !sent {
+                        *status = Status::Failed;
+                    }
+                }
+            }
+        }
+        if sent {
+            Ok(())
+        } else {
+            Err(error.unwrap_or_else(|| AbotError::Other("public message not sent".into())))
+        }
+    }
+}
+
+/// Sends the private message of a subscriber through every notifier, in the room the member
+/// alerts are routed to or in the thread of the first alert if any, and returns the id of the
+/// first message sent. A failing backend is logged and counted, an error is only returned
+/// if no backend sent the message.
+async fn send_private_report(
+    notifiers: &[&dyn Notifier],
+    subscriber: &str,
+    route: Option<&str>,
+    thread_root: Option<&str>,
+    report: &Report,
+    metrics: &Metrics,
+) -> Result<Option<EventID>, AbotError> {
+    let (message, formatted_message) = (report.message(), report.formatted_message());
+    let mut event_id: Option<EventID> = None;
+    let mut error: Option<AbotError> = None;
+    let mut sent = false;
+    for notifier in notifiers {
+        let result = match (route, thread_root) {
+            (Some(room_id), _) => {
+                notifier
+                    .send_routed_message(subscriber, room_id, &message, Some(&formatted_message))
+                    .await
+            }
+            (None, Some(root)) => {
+                notifier
+                    .send_private_thread_message(
+                        subscriber,
+                        &message,
+                        Some(&formatted_message),
+                        root,
+                    )
+                    .await
+            }
+            (None, None) => {
+                notifier
+                    .send_private_message(subscriber, &message, Some(&formatted_message))
+                    .await
+            }
+        };
+        match result {
+            // a backend the subscriber is not reachable on, e.g. no telegram chat linked,
+            // sends nothing and does not count as delivered
+            Ok(Some(id)) => {
+                sent = true;
+                event_id = event_id.or(Some(id));
+            }
+            Ok(None) => (),
+            Err(e) => {
+                warn!("Private message to {} not sent: {}", subscriber, e);
+                metrics.inc_failed();
+                error = Some(e);
+            }
+        }
+    }
+    if sent {
+        Ok(event_id)
+    } else {
+        Err(error.unwrap_or_else(|| AbotError::Other(format!("no message sent to {}", subscriber))))
+    }
+}
+
+/// Returns the field used to track the last time an alert has been sent to a subscriber.
+/// The message digest is SHA-256 so that keys stored in Redis stay the same across builds
+fn last_alert_key(dedup_key: &AlertDedupKey, code: u32, service_id: &str, message: &str) -> String {
+    match dedup_key {
+        AlertDedupKey::Code => format!("{}:{}", code, service_id),
+        AlertDedupKey::Content => {
+            let digest = Sha256::digest(message.as_bytes());
+            format!("{}:{}:{}", code, service_id, hex::encode(&digest[..8]))
+        }
+    }
+}
+
+/// Returns true if the alert code is snoozed and the snooze has not expired yet
+fn is_snoozed(snoozed_until: Option<i64>, now: i64) -> bool {
+    match snoozed_until {
+        Some(snoozed_until) => now < snoozed_until,
+        None => false,
+    }
+}
+
+/// Returns true if quiet hours are defined and the time falls inside them
+fn is_quiet(quiet_hours: Option<&QuietHours>, now: DateTime<Utc>) -> bool {
+    quiet_hours.is_some_and(|quiet_hours| quiet_hours.contains(now))
+}
+
+/// Returns true if the severity is below an active threshold. An expiry of 0 never expires.
+fn below_threshold(
+    severity: &Severity,
+    threshold: Option<Severity>,
+    threshold_expiry: i64,
+    now: i64,
+) -> bool {
+    match threshold {
+        Some(threshold) => {
+            (threshold_expiry == 0 || now < threshold_expiry) && *severity < threshold
+        }
+        None => false,
+    }
+}
+
+/// Returns true if the alert is from the chain (ignoring case), or if no chain is defined.
+/// The chain is read from the health check records, falling back to the service id prefix
+/// (e.g. polkadot-rpc) when the records do not include it.
+fn matches_chain(chain: Option<&str>, health_checks: &[Value], service_id: &str) -> bool {
+    let chain = match chain {
+        Some(chain) => chain,
+        None => return true,
+    };
+    let chains: Vec<&str> = health_checks
+        .iter()
+        .filter_map(|health_check| health_check.get("record")?.get("chain")?.as_str())
+        .collect();
+    if chains.is_empty() {
+        return service_id
+            .strip_prefix(chain)
+            .is_some_and(|rest| rest.starts_with('-'));
+    }
+    chains.iter().any(|c| c.trim().eq_ignore_ascii_case(chain))
+}
+
+/// Returns the key under which the callout of the alert is muted, per member and alert code:service
+/// (or content) as for the private delivery
+fn callout_key(alert: &Alert) -> CacheKey {
+    CacheKey::LastCallout(
+        alert.member_id.to_string(),
+        last_alert_key(
+            &CONFIG.alert_dedup_key,
+            alert.code,
+            &alert.service_id,
+            &alert.message,
+        ),
+    )
+}
+
+/// Describes an alert dropped because its service is not whitelisted
+fn not_whitelisted_message(alert: &Alert, subscribers: usize) -> String {
+    format!(
+        "Alert {} from member {} dropped for {} subscriber(s): service '{}' is not whitelisted",
+        alert.code, alert.member_id, subscribers, alert.service_id
+    )
+}
+
+/// Sends the notice privately to the admin users the first time the service is dropped
+async fn notify_admins_once(
+    abot: &Abot,
+    conn: &mut Connection,
+    service_id: &str,
+    notice: &str,
+) -> Result<(), ApiError> {
+    if CONFIG.admin_user_ids.is_empty() {
+        return Ok(());
+    }
+    let first_seen = redis::cmd("SET")
+        .arg(CacheKey::NotWhitelistedNotice(service_id.to_string()))
+        .arg(Utc::now().timestamp())
+        .arg("NX")
+        .query_async::<Connection, Option<String>>(conn)
+        .await
+        .map_err(CacheError::RedisCMDError)?
+        .is_some();
+    if first_seen {
+        for admin in CONFIG.admin_user_ids.iter() {
+            if let Err(e) = abot
+                .matrix()
+                .send_private_message(admin, notice, None)
+                .await
+            {
+                warn!("Not whitelisted notice to {} failed: {}", admin, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns true if the service is whitelisted, ignoring case and surrounding spaces
+fn is_whitelisted(service_id: &str) -> bool {
+    let service_id = normalize_service_id(service_id);
+    WHITELIST_SERVICES
+        .iter()
+        .any(|whitelisted| normalize_service_id(whitelisted) == service_id)
+}
+
+/// Returns the idempotency key of the request, if any
+fn idempotency_key(req: &HttpRequest) -> Result<Option<String>, ApiError> {
+    match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        None => Ok(None),
+        Some(hv) => {
+            let key = hv
+                .to_str()
+                .map_err(|_| ApiError::BadRequest("invalid Idempotency-Key header".into()))?
+                .trim();
+            if key.is_empty() || key.len() > IDEMPOTENCY_KEY_MAX_LEN {
+                return Err(ApiError::BadRequest(format!(
+                    "Idempotency-Key must have between 1 and {} characters",
+                    IDEMPOTENCY_KEY_MAX_LEN
+                )));
+            }
+            Ok(Some(key.to_string()))
+        }
+    }
+}
+
+/// Returns the start of the occurrences window and the number of occurrences including
+/// the current one, a new window starts once the previous one has elapsed
+fn next_occurrence(previous: Option<(i64, u32)>, now: i64, window: i64) -> (i64, u32) {
+    match previous {
+        Some((start, count)) if now < start + window => (start, count + 1),
+        _ => (now, 1),
+    }
+}
+
+/// Returns the severity escalated by the number of occurrences, severities are never lowered
+/// and a threshold of 0 disables the escalation to that severity
+fn escalated_severity(severity: &Severity, occurrences: u32, medium: u32, high: u32) -> Severity {
+    if high > 0 && occurrences >= high {
+        Severity::High
+    } else if medium > 0 && occurrences >= medium && *severity == Severity::Low {
+        Severity::Medium
+    } else {
+        severity.clone()
+    }
+}
+
+/// Reads the request body, rejecting bodies larger than limit bytes
+async fn read_body(payload: web::Payload, limit: usize) -> Result<web::Bytes, ApiError> {
+    match payload.to_bytes_limited(limit).await {
+        Ok(body) => body.map_err(|e| ApiError::BadRequest(e.to_string())),
+        Err(_) => Err(ApiError::PayloadTooLarge(format!(
+            "alert body must not exceed {} bytes",
+            limit
+        ))),
+    }
+}
+
+/// Verifies that the signature is the hex encoded HMAC-SHA256 of the body,
+/// an optional `sha256=` prefix is accepted. The comparison is constant-time.
+fn verify_signature(secret: &str, body: &[u8], signature: Option<&str>) -> Result<(), ApiError> {
+    let signature = match signature {
+        Some(signature) => signature,
+        None => return Err(ApiError::Unauthorized("signature is missing".into())),
+    };
+    let signature = signature.trim_start_matches("sha256=");
+    let signature = hex::decode(signature)
+        .map_err(|_| ApiError::Unauthorized("signature is invalid".into()))?;
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|e| ApiError::InternalServerError(e.to_string()))?;
+    mac.update(body);
+    mac.verify_slice(&signature)
+        .map_err(|_| ApiError::Unauthorized("signature is invalid".into()))
+}
+
+/// Returns the delivery status of an alert for a subscriber, or None if the alert is still muted
+fn delivery_status(paused: bool, last_time_sent: i64, mute_time: i64, now: i64) -> Option<Status> {
+    if paused {
+        return Some(Status::Skipped);
+    }
+    if now > last_time_sent + (mute_time * 60) {
+        return Some(Status::Delivered);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    // Notifier recording the messages sent, every call fails if failing is set
+    #[derive(Default)]
+    struct FakeNotifier {
+        private: Mutex<Vec<String>>,
+        public: Mutex<Vec<String>>,
+        failing: bool,
+        // like telegram without a linked chat or chat id, nothing is sent
+        unlinked: bool,
+    }
+
+    #[async_trait]
+    impl Notifier for FakeNotifier {
+        async fn send_private_message(
+            &self,
+            to_user_id: &str,
+            _message: &str,
+            _formatted_message: Option<&str>,
+        ) -> Result<Option<String>, AbotError> {
+            if self.failing {
+                return Err(AbotError::Other("backend down".into()));
+            }
+            if self.unlinked {
+                return Ok(None);
+            }
+            self.private.lock().unwrap().push(to_user_id.to_string());
+            Ok(Some("$private".into()))
+        }
+
+        async fn send_public_message(
+            &self,
+            message: &str,
+            _formatted_message: Option<&str>,
+        ) -> Result<Option<String>, AbotError> {
+            if self.failing {
+                return Err(AbotError::Other("backend down".into()));
+            }
+            if self.unlinked {
+                return Ok(None);
+            }
+            self.public.lock().unwrap().push(message.to_string());
+            Ok(Some("$public".into()))
+        }
+    }
+
+    fn alert() -> Alert {
+        Alert {
+            code: 100,
+            severity: Severity::High,
+            message: "RPC service is offline".into(),
+            member_id: "turboflakes".into(),
+            service_id: "polkadot-rpc".into(),
+            health_check_id: 1,
+            health_checks: vec![],
+        }
+    }
+
+    #[test]
+    fn it_accepts_a_valid_alert() {
+        assert_eq!(alert().validate(), Ok(()));
+    }
+
+    #[test]
+    fn it_rejects_an_alert_without_code() {
+        let a = Alert { code: 0, ..alert() };
+        assert!(matches!(a.validate(), Err(ApiError::BadRequest(_))));
+    }
+
+    #[test]
+    fn it_rejects_an_alert_with_empty_member_id() {
+        let a = Alert {
+            member_id: " ".into(),
+            ..alert()
+        };
+        assert!(matches!(a.validate(), Err(ApiError::BadRequest(_))));
+    }
+
+    #[test]
+    fn it_rejects_an_alert_with_empty_service_id() {
+        let a = Alert {
+            service_id: "".into(),
+            ..alert()
+        };
+        assert!(matches!(a.validate(), Err(ApiError::BadRequest(_))));
+    }
+
+    #[test]
+    fn it_rejects_an_alert_with_too_many_health_checks() {
+        let a = Alert {
+            health_checks: vec![Value::Null; MAX_HEALTH_CHECKS + 1],
+            ..alert()
+        };
+        assert!(matches!(a.validate(), Err(ApiError::BadRequest(_))));
+    }
+
+    #[test]
+    fn it_rejects_an_alert_with_unknown_severity() {
+        let data = r#"{"code":100,"severity":"critical","message":"","memberId":"turboflakes","serviceId":"polkadot-rpc","healthCheckId":1,"healthChecks":[]}"#;
+        assert!(serde_json::from_str::<Alert>(data).is_err());
+    }
+
+    #[test]
+    fn it_accepts_an_alert_without_optional_fields() {
+        let data =
+            r#"{"code":100,"severity":"high","memberId":"turboflakes","serviceId":"polkadot-rpc"}"#;
+        let a = serde_json::from_str::<Alert>(data).unwrap();
+        assert_eq!(a.message, "");
+        assert_eq!(a.health_check_id, 0);
+        assert!(a.health_checks.is_empty());
+        assert_eq!(a.validate(), Ok(()));
+    }
+
+    #[test]
+    fn it_rejects_an_alert_without_required_fields() {
+        let data = serde_json::json!({
+            "code": 100,
+            "severity": "high",
+            "memberId": "turboflakes",
+            "serviceId": "polkadot-rpc",
+        });
+        for field in ["code", "severity", "memberId", "serviceId"] {
+            let mut data = data.clone();
+            data.as_object_mut().unwrap().remove(field);
+            assert!(serde_json::from_value::<Alert>(data).is_err(), "{}", field);
+        }
+    }
+
+    #[test]
+    fn it_matches_whitelisted_services_ignoring_case_and_spaces() {
+        assert!(is_whitelisted("polkadot-rpc"));
+        assert!(is_whitelisted("Polkadot-RPC"));
+        assert!(is_whitelisted("polkadot-rpc "));
+        assert!(is_whitelisted(" KUSAMA-rpc\t"));
+        assert!(!is_whitelisted("polkadot-boot"));
+        assert!(!is_whitelisted("polkadot rpc"));
+    }
+
+    #[test]
+    fn it_matches_alerts_by_chain() {
+        let health_checks = vec![serde_json::json!({
+            "record": { "chain": "Polkadot", "endpoint": "rpc.ibp.network" }
+        })];
+        assert!(matches_chain(None, &health_checks, "polkadot-rpc"));
+        assert!(matches_chain(
+            Some("polkadot"),
+            &health_checks,
+            "polkadot-rpc"
+        ));
+        assert!(!matches_chain(
+            Some("statemint"),
+            &health_checks,
+            "polkadot-rpc"
+        ));
+        // without chain in the records the service id is used
+        assert!(matches_chain(Some("statemint"), &[], "statemint-rpc"));
+        assert!(!matches_chain(
+            Some("polkadot"),
+            &[],
+            "collectives-polkadot-rpc"
+        ));
+        assert!(!matches_chain(
+            Some("kusama"),
+            &[Value::Null],
+            "polkadot-rpc"
+        ));
+    }
+
+    #[test]
+    fn it_describes_alerts_dropped_for_services_not_whitelisted() {
+        let a = Alert {
+            service_id: "polkadot-boot".to_string(),
+            ..alert()
+        };
+        let notice = not_whitelisted_message(&a, 3);
+        assert!(notice.contains("'polkadot-boot' is not whitelisted"));
+        assert!(notice.contains("for 3 subscriber(s)"));
+        assert_eq!(
+            CacheKey::NotWhitelistedNotice("polkadot-boot".to_string()).to_string(),
+            "abot:not_whitelisted:polkadot-boot"
+        );
+    }
+
+    #[test]
+    fn it_skips_alerts_during_quiet_hours() {
+        let quiet_hours = QuietHours::parse("23:00-07:00", None);
+        let at = |time: &str| {
+            DateTime::parse_from_rfc3339(&format!("2023-06-01T{}:00Z", time))
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        assert!(is_quiet(quiet_hours.as_ref(), at("02:00")));
+        assert!(!is_quiet(quiet_hours.as_ref(), at("12:00")));
+        assert!(!is_quiet(None, at("02:00")));
+    }
+
+    #[test]
+    fn it_keys_last_alerts_by_code() {
+        let a = last_alert_key(&AlertDedupKey::Code, 100, "polkadot-rpc", "offline");
+        let b = last_alert_key(&AlertDedupKey::Code, 100, "polkadot-rpc", "timeout");
+        assert_eq!(a, "100:polkadot-rpc");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn it_keys_last_alerts_by_content() {
+        let a = last_alert_key(&AlertDedupKey::Content, 100, "polkadot-rpc", "offline");
+        let b = last_alert_key(&AlertDedupKey::Content, 100, "polkadot-rpc", "timeout");
+        let c = last_alert_key(&AlertDedupKey::Content, 100, "polkadot-rpc", "offline");
+        assert_eq!(a, "100:polkadot-rpc:8e2c7ac508139a02");
+        assert_ne!(a, b);
+        assert_eq!(a, c);
+    }
+
+    #[test]
+    fn it_skips_delivery_while_paused() {
+        assert_eq!(delivery_status(true, 0, 5, 1000), Some(Status::Skipped));
+        assert_eq!(delivery_status(true, 900, 5, 1000), Some(Status::Skipped));
+    }
+
+    #[test]
+    fn it_delivers_after_mute_interval() {
+        assert_eq!(delivery_status(false, 0, 5, 1000), Some(Status::Delivered));
+        assert_eq!(
+            delivery_status(false, 600, 5, 1000),
+            Some(Status::Delivered)
+        );
+        assert_eq!(delivery_status(false, 700, 5, 1000), None);
+        assert_eq!(
+            delivery_status(false, 700, 5, 1001),
+            Some(Status::Delivered)
+        );
+    }
+
+    fn sign(secret: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    #[actix_web::test]
+    async fn it_rejects_an_oversized_body() {
+        use actix_web::{http::StatusCode, test, App, HttpResponse};
+
+        let app = test::init_service(App::new().route(
+            "/alerts",
+            web::post().to(|payload: web::Payload| async move {
+                read_body(payload, 16)
+                    .await
+                    .map(|body| HttpResponse::Ok().body(body))
+            }),
+        ))
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/alerts")
+            .set_payload(r#"{"code":100}"#)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::post()
+            .uri("/alerts")
+            .set_payload(vec![b'x'; 17])
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], 413);
+    }
+
+    #[test]
+    fn it_counts_occurrences_within_the_window() {
+        assert_eq!(next_occurrence(None, 1000, 600), (1000, 1));
+        assert_eq!(next_occurrence(Some((1000, 1)), 1300, 600), (1000, 2));
+        assert_eq!(next_occurrence(Some((1000, 2)), 1599, 600), (1000, 3));
+        // the window resets once elapsed
+        assert_eq!(next_occurrence(Some((1000, 3)), 1600, 600), (1600, 1));
+    }
+
+    #[test]
+    fn it_escalates_repeated_alerts() {
+        assert_eq!(escalated_severity(&Severity::Low, 2, 3, 6), Severity::Low);
+        assert_eq!(
+            escalated_severity(&Severity::Low, 3, 3, 6),
+            Severity::Medium
+        );
+        assert_eq!(escalated_severity(&Severity::Low, 6, 3, 6), Severity::High);
+        assert_eq!(
+            escalated_severity(&Severity::Medium, 3, 3, 6),
+            Severity::Medium
+        );
+        assert_eq!(
+            escalated_severity(&Severity::Medium, 6, 3, 6),
+            Severity::High
+        );
+        assert_eq!(escalated_severity(&Severity::High, 1, 3, 6), Severity::High);
+        // a threshold of 0 never escalates to that severity
+        assert_eq!(escalated_severity(&Severity::Low, 10, 0, 6), Severity::High);
+        assert_eq!(
+            escalated_severity(&Severity::Low, 10, 3, 0),
+            Severity::Medium
+        );
+    }
+
+    #[test]
+    fn it_accepts_a_valid_signature() {
+        let body = br#"{"code":100}"#;
+        let signature = sign("secret", body);
+        assert_eq!(verify_signature("secret", body, Some(&signature)), Ok(()));
+        let prefixed = format!("sha256={}", signature);
+        assert_eq!(verify_signature("secret", body, Some(&prefixed)), Ok(()));
+    }
+
+    #[test]
+    fn it_rejects_an_invalid_signature() {
+        let body = br#"{"code":100}"#;
+        let signature = sign("other-secret", body);
+        assert_eq!(
+            verify_signature("secret", body, Some(&signature)),
+            Err(ApiError::Unauthorized("signature is invalid".into()))
+        );
+        assert_eq!(
+            verify_signature("secret", body, Some("not-hex")),
+            Err(ApiError::Unauthorized("signature is invalid".into()))
+        );
+    }
+
+    #[test]
+    fn it_reads_the_idempotency_key() {
+        use actix_web::test::TestRequest;
+
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(idempotency_key(&req), Ok(None));
+        let req = TestRequest::default()
+            .insert_header((IDEMPOTENCY_KEY_HEADER, " alert-42 "))
+            .to_http_request();
+        assert_eq!(idempotency_key(&req), Ok(Some("alert-42".to_string())));
+        let req = TestRequest::default()
+            .insert_header((IDEMPOTENCY_KEY_HEADER, ""))
+            .to_http_request();
+        assert!(matches!(
+            idempotency_key(&req),
+            Err(ApiError::BadRequest(_))
+        ));
+        let req = TestRequest::default()
+            .insert_header((
+                IDEMPOTENCY_KEY_HEADER,
+                "x".repeat(IDEMPOTENCY_KEY_MAX_LEN + 1),
+            ))
+            .to_http_request();
+        assert!(matches!(
+            idempotency_key(&req),
+            Err(ApiError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn it_rejects_a_missing_signature() {
+        assert_eq!(
+            verify_signature("secret", b"{}", None),
+            Err(ApiError::Unauthorized("signature is missing".into()))
+        );
+    }
+
+    #[test]
+    fn it_serializes_event_id_of_delivered_alerts() {
+        let response = Response {
+            data: vec![
+                (
+                    "@alice:matrix.org".into(),
+                    Status::Delivered,
+                    Some("$event".into()),
+                ),
+                ("@bob:matrix.org".into(), Status::Skipped, None),
+            ],
+        };
+        assert_eq!(
+            serde_json::to_string(&response).unwrap(),
+            r#"{"data":[["@alice:matrix.org","delivered","$event"],["@bob:matrix.org","skipped",null]]}"#
+        );
+    }
+
+    #[actix_web::test]
+    async fn it_sends_the_public_message_once_for_all_public_subscribers() {
+        let notifier = FakeNotifier::default();
+        let report = Report::from(RawAlert::preview(Severity::High));
+        let mut resp_data: Vec<(UserID, Status, Option<EventID>)> = vec![
+            ("@alice:matrix.org".into(), Status::Delivered, None),
+            (
+                "@bob:matrix.org".into(),
+                Status::Delivered,
+                Some("$private".into()),
+            ),
+            ("@carol:matrix.org".into(), Status::Delivered, None),
+        ];
+        let mut public = PublicDelivery::default();
+        public.request(0, &report);
+        public.request(1, &report);
+        public
+            .send(&[&notifier], &mut resp_data, &Metrics::new())
+            .await
+            .unwrap();
+
+        assert_eq!(notifier.public.lock().unwrap().len(), 1);
+        assert_eq!(resp_data[0].2, Some("$public".to_string()));
+        // subscribers also delivered privately keep the id of the private message
+        assert_eq!(resp_data[1].2, Some("$private".to_string()));
+        assert_eq!(resp_data[2].2, None);
+
+        // nothing is sent if no subscriber asked for public delivery
+        PublicDelivery::default()
+            .send(&[&notifier], &mut resp_data, &Metrics::new())
+            .await
+            .unwrap();
+        assert_eq!(notifier.public.lock().unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn it_delivers_privately_unless_every_backend_failed() {
+        let report = Report::from(RawAlert::preview(Severity::High));
+        let metrics = Metrics::new();
+        let failing = FakeNotifier {
+            failing: true,
+            ..Default::default()
+        };
+        let working = FakeNotifier::default();
+
+        // matrix failing after telegram delivered, or the other way around, still delivers
+        for notifiers in [[&failing as &dyn Notifier, &working], [&working, &failing]] {
+            let event_id = send_private_report(
+                &notifiers,
+                "@alice:matrix.org",
+                None,
+                None,
+                &report,
+                &metrics,
+            )
+            .await
+            .unwrap();
+            assert_eq!(event_id, Some("$private".to_string()));
+        }
+        assert_eq!(working.private.lock().unwrap().len(), 2);
+
+        assert!(send_private_report(
+            &[&failing],
+            "@alice:matrix.org",
+            None,
+            None,
+            &report,
+            &metrics
+        )
+        .await
+        .is_err());
+    }
+
+    #[actix_web::test]
+    async fn it_fails_public_only_subscribers_if_every_backend_failed() {
+        let report = Report::from(RawAlert::preview(Severity::High));
+        let failing = FakeNotifier {
+            failing: true,
+            ..Default::default()
+        };
+        let mut resp_data: Vec<(UserID, Status, Option<EventID>)> = vec![
+            ("@alice:matrix.org".into(), Status::Delivered, None),
+            (
+                "@bob:matrix.org".into(),
+                Status::Delivered,
+                Some("$private".into()),
+            ),
+        ];
+        let mut public = PublicDelivery::default();
+        public.request(0, &report);
+        public.request(1, &report);
+        assert!(public
+            .send(&[&failing], &mut resp_data, &Metrics::new())
+            .await
+            .is_err());
+        assert_eq!(resp_data[0].1, Status::Failed);
+        assert_eq!(resp_data[1].1, Status::Delivered);
+    }
+
+    #[actix_web::test]
+    async fn it_does_not_deliver_when_matrix_failed_and_telegram_is_unlinked() {
+        let report = Report::from(RawAlert::preview(Severity::High));
+        let matrix = FakeNotifier {
+            failing: true,
+            ..Default::default()
+        };
+        let telegram = FakeNotifier {
+            unlinked: true,
+            ..Default::default()
+        };
+        let notifiers = [&matrix as &dyn Notifier, &telegram];
+
+        assert!(send_private_report(
+            &notifiers,
+            "@alice:matrix.org",
+            None,
+            None,
+            &report,
+            &Metrics::new()
+        )
+        .await
+        .is_err());
+        // nothing is sent either when no backend reaches the subscriber
+        assert!(send_private_report(
+            &[&telegram],
+            "@alice:matrix.org",
+            None,
+            None,
+            &report,
+            &Metrics::new()
+        )
+        .await
+        .is_err());
+
+        let mut resp_data: Vec<(UserID, Status, Option<EventID>)> =
+            vec![("@alice:matrix.org".into(), Status::Delivered, None)];
+        let mut public = PublicDelivery::default();
+        public.request(0, &report);
+        assert!(public
+            .send(&notifiers, &mut resp_data, &Metrics::new())
+            .await
+            .is_err());
+        assert_eq!(resp_data[0].1, Status::Failed);
+    }
+
+    #[actix_web::test]
+    async fn it_processes_the_retry_of_a_failed_alert_with_the_same_idempotency_key() {
+        let keys: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+        let claim = || async { Ok::<bool, String>(keys.lock().unwrap().insert("key".into())) };
+        let release = || async {
+            keys.lock().unwrap().remove("key");
+            Ok(())
+        };
+
+        // the first attempt fails, e.g. the homeserver could not be reached
+        let first = once_per_key(
+            claim(),
+            async { Err::<u32, _>("unreachable".into()) },
+            release,
+        )
+        .await;
+        assert_eq!(first, Err("unreachable".to_string()));
+        assert!(keys.lock().unwrap().is_empty());
+
+        // the retry of the monitor is delivered
+        let retry = once_per_key(claim(), async { Ok(1) }, release).await;
+        assert_eq!(retry, Ok(Some(1)));
+
+        // further retries are duplicates
+        let duplicate = once_per_key(claim(), async { Ok(2) }, release).await;
+        assert_eq!(duplicate, Ok(None));
+        assert!(keys.lock().unwrap().contains("key"));
+    }
+
+    #[actix_web::test]
+    async fn it_calls_out_the_same_alert_once() {
+        let keys: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+        let callouts = Mutex::new(Vec::new());
+        let post = |alert: Alert| {
+            let key = callout_key(&alert).to_string();
+            let keys = &keys;
+            let callouts = &callouts;
+            async move {
+                once_per_key(
+                    async { Ok::<bool, String>(keys.lock().unwrap().insert(key.clone())) },
+                    async {
+                        callouts.lock().unwrap().push(alert.code);
+                        Ok(())
+                    },
+                    || async {
+                        keys.lock().unwrap().remove(&key);
+                        Ok(())
+                    },
+                )
+                .await
+            }
+        };
+
+        // the same alert posted twice is called out once
+        assert_eq!(post(alert()).await, Ok(Some(())));
+        assert_eq!(post(alert()).await, Ok(None));
+        assert_eq!(*callouts.lock().unwrap(), vec![100]);
+
+        // another code or member is called out
+        let other_code = Alert {
+            code: 101,
+            ..alert()
+        };
+        let other_member = Alert {
+            member_id: "stakeworld".into(),
+            ..alert()
+        };
+        assert_eq!(post(other_code).await, Ok(Some(())));
+        assert_eq!(post(other_member).await, Ok(Some(())));
+        assert_eq!(*callouts.lock().unwrap(), vec![100, 101, 100]);
+    }
+
+    #[test]
+    fn it_serializes_reinvited_alerts() {
+        let response = Response {
+            data: vec![(
+                "@alice:matrix.org".into(),
+                Status::Reinvited,
+                Some("$event".into()),
+            )],
+        };
+        assert_eq!(
+            serde_json::to_string(&response).unwrap(),
+            r#"{"data":[["@alice:matrix.org","reinvited","$event"]]}"#
+        );
+    }
+
+    #[test]
+    fn it_serializes_pending_alerts() {
+        let response = Response {
+            data: vec![("@alice:matrix.org".into(), Status::Pending, None)],
+        };
+        assert_eq!(
+            serde_json::to_string(&response).unwrap(),
+            r#"{"data":[["@alice:matrix.org","pending",null]]}"#
+        );
+    }
+
+    #[test]
+    fn it_skips_alerts_below_threshold() {
+        assert!(below_threshold(
+            &Severity::Low,
+            Some(Severity::High),
+            0,
+            100
+        ));
+        assert!(below_threshold(
+            &Severity::Medium,
+            Some(Severity::High),
+            200,
+            100
+        ));
+        assert!(!below_threshold(
+            &Severity::High,
+            Some(Severity::High),
+            0,
+            100
+        ));
+        assert!(!below_threshold(
+            &Severity::High,
+            Some(Severity::Medium),
+            0,
+            100
+        ));
+        assert!(!below_threshold(&Severity::Low, None, 0, 100));
+    }
+
+    #[test]
+    fn it_delivers_alerts_after_threshold_expires() {
+        assert!(!below_threshold(
+            &Severity::Low,
+            Some(Severity::High),
+            100,
+            100
+        ));
+        assert!(!below_threshold(
+            &Severity::Low,
+            Some(Severity::High),
+            50,
+            100
+        ));
+    }
+
+    #[test]
+    fn it_skips_snoozed_codes_only() {
+        assert!(is_snoozed(Some(200), 100));
+        assert!(!is_snoozed(Some(100), 100));
+        assert!(!is_snoozed(None, 100));
+    }
 }
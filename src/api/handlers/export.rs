@@ -0,0 +1,419 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::api::helpers::respond_json;
+use crate::cache::{get_conn, RedisConn};
+use crate::errors::{ApiError, CacheError};
+use crate::Abot;
+use actix_web::{web, web::Json};
+use redis::aio::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Current version of the export document
+const EXPORT_VERSION: u32 = 1;
+
+// Key patterns scanned for the subscription state, see exportable_key_kind for the keys kept
+const EXPORT_KEY_PATTERNS: [&str; 3] = [
+    "abot:subscribers:*",
+    "abot:subscriber:*:config",
+    "abot:maintenance:*",
+];
+
+// Number of keys requested on each SCAN iteration
+const SCAN_COUNT: u32 = 100;
+
+/// Export represents all the subscription state cached in Redis
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Export {
+    version: u32,
+    sets: BTreeMap<String, BTreeSet<String>>,
+    hashes: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Export {
+    /// Rejects documents from other versions or with keys outside the subscription state
+    fn validate(&self) -> Result<(), ApiError> {
+        if self.version != EXPORT_VERSION {
+            return Err(ApiError::BadRequest(format!(
+                "export version {} not supported",
+                self.version
+            )));
+        }
+        for (key, kind) in self.keys() {
+            match exportable_key_kind(key) {
+                Some(key_kind) if key_kind == kind => (),
+                Some(key_kind) => {
+                    return Err(ApiError::BadRequest(format!(
+                        "key {} must be a {}",
+                        key,
+                        key_kind.as_str()
+                    )))
+                }
+                None => return Err(ApiError::BadRequest(format!("key {} not supported", key))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the keys of the document with the type they are imported as
+    fn keys(&self) -> Vec<(&String, KeyKind)> {
+        self.sets
+            .keys()
+            .map(|key| (key, KeyKind::Set))
+            .chain(self.hashes.keys().map(|key| (key, KeyKind::Hash)))
+            .collect()
+    }
+
+    /// Returns the first key that already exists in Redis with another type, given the
+    /// current types of the keys in the same order, with the type found
+    fn conflicting_key<'a>(&self, types: &'a [String]) -> Option<(&String, &'a str)> {
+        self.keys()
+            .into_iter()
+            .zip(types.iter())
+            .find(|((_, kind), key_type)| *key_type != "none" && *key_type != kind.as_str())
+            .map(|((key, _), key_type)| (key, key_type.as_str()))
+    }
+
+    /// Returns the commands restoring the document in a MULTI/EXEC transaction. Redis does
+    /// not roll back commands failing at runtime, so the key types are checked beforehand
+    /// while the keys are watched, see post_import
+    fn import_pipeline(&self) -> redis::Pipeline {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for (key, members) in self.sets.iter().filter(|(_, m)| !m.is_empty()) {
+            pipe.cmd("SADD")
+                .arg(key)
+                .arg(members.iter().collect::<Vec<_>>())
+                .ignore();
+        }
+        for (key, fields) in self.hashes.iter().filter(|(_, f)| !f.is_empty()) {
+            pipe.cmd("HSET").arg(key).arg(fields).ignore();
+        }
+        pipe
+    }
+}
+
+/// Redis type of an exported key
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeyKind {
+    Set,
+    Hash,
+}
+
+impl KeyKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Set => "set",
+            Self::Hash => "hash",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ImportResponse {
+    sets: usize,
+    hashes: usize,
+}
+
+/// Returns the type of the key if it holds subscription state:
+/// abot:subscribers:{member}:{severity} (Set), abot:subscriber:{who}:{member}:{severity}:config (Hash),
+/// abot:maintenance:{member} (Hash) and abot:maintenance:{member}:subscribers (Set).
+/// Other keys under the same prefixes, e.g. the maintenance queue (List), are not exported
+fn exportable_key_kind(key: &str) -> Option<KeyKind> {
+    if let Some(rest) = key.strip_prefix("abot:subscribers:") {
+        return (!rest.is_empty()).then_some(KeyKind::Set);
+    }
+    if let Some(rest) = key
+        .strip_prefix("abot:subscriber:")
+        .and_then(|rest| rest.strip_suffix(":config"))
+    {
+        return (!rest.is_empty()).then_some(KeyKind::Hash);
+    }
+    let member = key.strip_prefix("abot:maintenance:")?;
+    match member.strip_suffix(":subscribers") {
+        Some(member) if !member.is_empty() && !member.contains(':') => Some(KeyKind::Set),
+        None if !member.is_empty() && !member.contains(':') => Some(KeyKind::Hash),
+        _ => None,
+    }
+}
+
+async fn scan_keys(conn: &mut RedisConn, pattern: &str) -> Result<Vec<String>, CacheError> {
+    let mut keys: Vec<String> = Vec::new();
+    let mut cursor: u64 = 0;
+    loop {
+        let (next_cursor, batch) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(SCAN_COUNT)
+            .query_async::<Connection, (u64, Vec<String>)>(conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+        keys.extend(batch);
+        if next_cursor == 0 {
+            return Ok(keys);
+        }
+        cursor = next_cursor;
+    }
+}
+
+/// Handler to export all subscriptions, subscriber configs and maintenance state
+pub async fn get_export(abot: web::Data<Abot>) -> Result<Json<Export>, ApiError> {
+    let mut conn = get_conn(&abot.cache).await?;
+    let mut export = Export {
+        version: EXPORT_VERSION,
+        ..Default::default()
+    };
+
+    for pattern in EXPORT_KEY_PATTERNS {
+        for key in scan_keys(&mut conn, pattern).await? {
+            let kind = match exportable_key_kind(&key) {
+                Some(kind) => kind,
+                None => continue,
+            };
+            let key_type = redis::cmd("TYPE")
+                .arg(&key)
+                .query_async::<Connection, String>(&mut conn)
+                .await
+                .map_err(CacheError::RedisCMDError)?;
+            if key_type != kind.as_str() {
+                continue;
+            }
+
+            match kind {
+                KeyKind::Set => {
+                    let members = redis::cmd("SMEMBERS")
+                        .arg(&key)
+                        .query_async::<Connection, BTreeSet<String>>(&mut conn)
+                        .await
+                        .map_err(CacheError::RedisCMDError)?;
+                    export.sets.insert(key, members);
+                }
+                KeyKind::Hash => {
+                    let fields = redis::cmd("HGETALL")
+                        .arg(&key)
+                        .query_async::<Connection, BTreeMap<String, String>>(&mut conn)
+                        .await
+                        .map_err(CacheError::RedisCMDError)?;
+                    export.hashes.insert(key, fields);
+                }
+            }
+        }
+    }
+
+    respond_json(export)
+}
+
+/// Handler to import a previously exported document, existing state is merged and not removed.
+/// The keys are watched while their types are checked, so the transaction is aborted if any of
+/// them changes before it runs and no command can fail with WRONGTYPE
+pub async fn post_import(
+    export: web::Json<Export>,
+    abot: web::Data<Abot>,
+) -> Result<Json<ImportResponse>, ApiError> {
+    export.validate()?;
+    let mut conn = get_conn(&abot.cache).await?;
+
+    let keys: Vec<&String> = export.keys().into_iter().map(|(key, _)| key).collect();
+    if !keys.is_empty() {
+        redis::cmd("WATCH")
+            .arg(&keys)
+            .query_async::<Connection, ()>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+
+        let mut pipe = redis::pipe();
+        for key in keys.iter() {
+            pipe.cmd("TYPE").arg(key);
+        }
+        let types = pipe
+            .query_async::<Connection, Vec<String>>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+
+        if let Some((key, key_type)) = export.conflicting_key(&types) {
+            redis::cmd("UNWATCH")
+                .query_async::<Connection, ()>(&mut conn)
+                .await
+                .map_err(CacheError::RedisCMDError)?;
+            return Err(ApiError::BadRequest(format!(
+                "key {} already exists as a {}",
+                key, key_type
+            )));
+        }
+    }
+
+    // EXEC returns nil when a watched key changed after the type check
+    let applied = export
+        .import_pipeline()
+        .query_async::<Connection, Option<()>>(&mut conn)
+        .await
+        .map_err(CacheError::RedisCMDError)?;
+    if applied.is_none() {
+        return Err(ApiError::ServiceUnavailable(
+            "keys changed during the import, nothing has been imported".to_string(),
+        ));
+    }
+
+    respond_json(ImportResponse {
+        sets: export.sets.len(),
+        hashes: export.hashes.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abot::Severity;
+    use crate::cache::CacheKey;
+
+    fn export() -> Export {
+        let mut export = Export {
+            version: EXPORT_VERSION,
+            ..Default::default()
+        };
+        export.sets.insert(
+            CacheKey::Subscribers("turboflakes".into(), Severity::High).to_string(),
+            BTreeSet::from(["@alice:matrix.org".to_string()]),
+        );
+        export.sets.insert(
+            CacheKey::MaintenanceSubscribers("turboflakes".into()).to_string(),
+            BTreeSet::from(["@bob:matrix.org".to_string()]),
+        );
+        export.hashes.insert(
+            CacheKey::SubscriberConfig(
+                "@alice:matrix.org".into(),
+                "turboflakes".into(),
+                Severity::High,
+            )
+            .to_string(),
+            BTreeMap::from([("mute".to_string(), "5".to_string())]),
+        );
+        export.hashes.insert(
+            CacheKey::Maintenance("turboflakes".into()).to_string(),
+            BTreeMap::from([("mode".to_string(), "on".to_string())]),
+        );
+        export
+    }
+
+    #[test]
+    fn it_round_trips_an_export() {
+        let export = export();
+        let json = serde_json::to_string(&export).unwrap();
+        let import: Export = serde_json::from_str(&json).unwrap();
+        assert_eq!(import, export);
+        assert_eq!(import.validate(), Ok(()));
+    }
+
+    #[test]
+    fn it_matches_exportable_keys() {
+        assert_eq!(
+            exportable_key_kind("abot:subscribers:turboflakes:high"),
+            Some(KeyKind::Set)
+        );
+        assert_eq!(
+            exportable_key_kind("abot:subscriber:@alice:matrix.org:turboflakes:high:config"),
+            Some(KeyKind::Hash)
+        );
+        assert_eq!(
+            exportable_key_kind("abot:maintenance:turboflakes"),
+            Some(KeyKind::Hash)
+        );
+        assert_eq!(
+            exportable_key_kind("abot:maintenance:turboflakes:subscribers"),
+            Some(KeyKind::Set)
+        );
+        // the maintenance queue is a list and is not exported
+        assert_eq!(
+            exportable_key_kind(&CacheKey::MaintenanceQueue("turboflakes".into()).to_string()),
+            None
+        );
+        assert_eq!(exportable_key_kind("abot:members"), None);
+        assert_eq!(exportable_key_kind("abot:paused:@alice:matrix.org"), None);
+        assert_eq!(exportable_key_kind("abot:maintenance:"), None);
+    }
+
+    #[test]
+    fn it_rejects_an_invalid_import() {
+        let mut import = export();
+        import.version = 2;
+        assert!(matches!(import.validate(), Err(ApiError::BadRequest(_))));
+
+        let mut import = export();
+        import
+            .sets
+            .insert("abot:members".into(), BTreeSet::from(["x".to_string()]));
+        assert!(matches!(import.validate(), Err(ApiError::BadRequest(_))));
+
+        // a set matching the maintenance pattern would fail with WRONGTYPE on the queue list
+        let mut import = export();
+        import.sets.insert(
+            CacheKey::MaintenanceQueue("turboflakes".into()).to_string(),
+            BTreeSet::from(["x".to_string()]),
+        );
+        assert!(matches!(import.validate(), Err(ApiError::BadRequest(_))));
+
+        // keys must be imported with their own type
+        let mut import = export();
+        import.hashes.insert(
+            CacheKey::MaintenanceSubscribers("turboflakes".into()).to_string(),
+            BTreeMap::from([("x".to_string(), "y".to_string())]),
+        );
+        assert!(matches!(import.validate(), Err(ApiError::BadRequest(_))));
+    }
+
+    #[test]
+    fn it_finds_keys_existing_with_another_type() {
+        let import = export();
+        // keys are listed sets first, then hashes, each sorted
+        let keys: Vec<(String, KeyKind)> = import
+            .keys()
+            .into_iter()
+            .map(|(key, kind)| (key.to_string(), kind))
+            .collect();
+        assert_eq!(keys.len(), 4);
+        assert_eq!(keys[0].1, KeyKind::Set);
+        assert_eq!(keys[3].1, KeyKind::Hash);
+
+        let types = |types: [&str; 4]| types.map(String::from).to_vec();
+        assert_eq!(
+            import.conflicting_key(&types(["set", "none", "hash", "none"])),
+            None
+        );
+        let conflicting = types(["set", "list", "hash", "none"]);
+        assert_eq!(
+            import.conflicting_key(&conflicting),
+            Some((&keys[1].0, "list"))
+        );
+    }
+
+    #[test]
+    fn it_imports_in_a_single_transaction() {
+        let pipe = export().import_pipeline();
+        let packed = String::from_utf8(pipe.get_packed_pipeline()).unwrap();
+        assert!(packed.starts_with("*1\r\n$5\r\nMULTI\r\n"));
+        assert!(packed.ends_with("*1\r\n$4\r\nEXEC\r\n"));
+        assert_eq!(packed.matches("SADD").count(), 2);
+        assert_eq!(packed.matches("HSET").count(), 2);
+    }
+}
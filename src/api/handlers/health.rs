@@ -0,0 +1,90 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::api::helpers::respond_json;
+use crate::errors::ApiError;
+use crate::Abot;
+use actix_web::web::{self, Json};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    Degraded,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct HealthResponse {
+    pub status: HealthStatus,
+    pub matrix: HealthStatus,
+    pub monitor: HealthStatus,
+}
+
+impl HealthResponse {
+    fn new(matrix_degraded: bool, monitor_stale: bool) -> HealthResponse {
+        let status = |degraded| {
+            if degraded {
+                HealthStatus::Degraded
+            } else {
+                HealthStatus::Ok
+            }
+        };
+        HealthResponse {
+            status: status(matrix_degraded || monitor_stale),
+            matrix: status(matrix_degraded),
+            monitor: status(monitor_stale),
+        }
+    }
+}
+
+/// Handler to report whether alerts are being delivered
+pub async fn get_health(abot: web::Data<Abot>) -> Result<Json<HealthResponse>, ApiError> {
+    respond_json(HealthResponse::new(
+        abot.matrix().is_degraded(),
+        abot.watchdog.is_stale(Instant::now()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reports_a_degraded_matrix() {
+        let response = serde_json::to_value(HealthResponse::new(true, false)).unwrap();
+        assert_eq!(
+            response,
+            serde_json::json!({ "status": "degraded", "matrix": "degraded", "monitor": "ok" })
+        );
+        assert_eq!(HealthResponse::new(false, false).status, HealthStatus::Ok);
+    }
+
+    #[test]
+    fn it_reports_a_stale_monitor() {
+        let response = serde_json::to_value(HealthResponse::new(false, true)).unwrap();
+        assert_eq!(
+            response,
+            serde_json::json!({ "status": "degraded", "matrix": "ok", "monitor": "degraded" })
+        );
+    }
+}
@@ -0,0 +1,36 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::abot::{announce_new_members, try_fetch_members_from_remote_url, MembersRefresh};
+use crate::api::helpers::respond_json;
+use crate::errors::ApiError;
+use crate::Abot;
+use actix_web::{web, web::Json};
+use log::error;
+
+/// Handler to refresh the cached members from the remote members.json file
+pub async fn post_members_refresh(abot: web::Data<Abot>) -> Result<Json<MembersRefresh>, ApiError> {
+    let refresh = try_fetch_members_from_remote_url(&abot.cache).await?;
+    if let Err(e) = announce_new_members(abot.matrix(), &refresh).await {
+        error!("announce new members error: {}", e);
+    }
+    respond_json(refresh)
+}
@@ -20,4 +20,11 @@
 // SOFTWARE.
 
 pub mod alerts;
+pub mod export;
+pub mod health;
 pub mod index;
+pub mod members;
+pub mod metrics;
+pub mod openapi;
+pub mod stats;
+pub mod subscribers;
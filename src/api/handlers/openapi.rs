@@ -0,0 +1,223 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::abot::Severity;
+use crate::api::handlers::alerts::{
+    Status, IDEMPOTENCY_KEY_HEADER, IDEMPOTENCY_KEY_MAX_LEN, MAX_HEALTH_CHECKS, SIGNATURE_HEADER,
+};
+use actix_web::HttpResponse;
+use serde_json::{json, Value};
+use std::env;
+
+/// Handler to get the OpenAPI description of the alert endpoint
+pub async fn get_openapi() -> HttpResponse {
+    HttpResponse::Ok().json(openapi_document())
+}
+
+/// Returns the OpenAPI document, enum values are taken from the serialized types
+/// so that the document follows any change to them
+fn openapi_document() -> Value {
+    let severities: Vec<Value> = [Severity::High, Severity::Medium, Severity::Low]
+        .iter()
+        .map(|severity| json!(severity))
+        .collect();
+    let statuses: Vec<Value> = [Status::Delivered, Status::Skipped, Status::Pending]
+        .iter()
+        .map(|status| json!(status))
+        .collect();
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": env!("CARGO_PKG_NAME"),
+            "description": env!("CARGO_PKG_DESCRIPTION"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "url": "/api/v1" }],
+        "security": [{ "apiKey": [] }],
+        "paths": {
+            "/alerts": {
+                "post": {
+                    "summary": "Deliver an alert to the member subscribers",
+                    "parameters": [
+                        {
+                            "name": SIGNATURE_HEADER,
+                            "in": "header",
+                            "required": false,
+                            "description": "Hex encoded HMAC-SHA256 of the body, required when the bot is configured with an HMAC secret",
+                            "schema": { "type": "string" },
+                        },
+                        {
+                            "name": IDEMPOTENCY_KEY_HEADER,
+                            "in": "header",
+                            "required": false,
+                            "description": "Alerts received again with the same key are not delivered twice",
+                            "schema": { "type": "string", "maxLength": IDEMPOTENCY_KEY_MAX_LEN },
+                        },
+                    ],
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": {
+                                "schema": { "$ref": "#/components/schemas/Alert" },
+                            },
+                        },
+                    },
+                    "responses": {
+                        "200": {
+                            "description": "Delivery status by subscriber",
+                            "content": {
+                                "application/json": {
+                                    "schema": { "$ref": "#/components/schemas/Response" },
+                                },
+                            },
+                        },
+                        "400": { "$ref": "#/components/responses/Error" },
+                        "401": { "$ref": "#/components/responses/Error" },
+                        "413": { "$ref": "#/components/responses/Error" },
+                        "500": { "$ref": "#/components/responses/Error" },
+                    },
+                },
+            },
+        },
+        "components": {
+            "securitySchemes": {
+                "apiKey": { "type": "apiKey", "in": "header", "name": "X-API-KEY" },
+            },
+            "schemas": {
+                "Severity": { "type": "string", "enum": severities },
+                "Status": { "type": "string", "enum": statuses },
+                "Alert": {
+                    "type": "object",
+                    "required": [
+                        "code",
+                        "severity",
+                        "message",
+                        "memberId",
+                        "serviceId",
+                        "healthCheckId",
+                        "healthChecks",
+                    ],
+                    "properties": {
+                        "code": { "type": "integer", "minimum": 1 },
+                        "severity": { "$ref": "#/components/schemas/Severity" },
+                        "message": { "type": "string" },
+                        "memberId": { "type": "string", "minLength": 1 },
+                        "serviceId": { "type": "string", "minLength": 1 },
+                        "healthCheckId": { "type": "integer", "minimum": 0 },
+                        "healthChecks": {
+                            "type": "array",
+                            "items": { "type": "object" },
+                            "maxItems": MAX_HEALTH_CHECKS,
+                        },
+                    },
+                    "example": {
+                        "code": 100,
+                        "severity": "high",
+                        "message": "Service offline",
+                        "memberId": "turboflakes",
+                        "serviceId": "polkadot-rpc",
+                        "healthCheckId": 1,
+                        "healthChecks": [],
+                    },
+                },
+                "Response": {
+                    "type": "object",
+                    "required": ["data"],
+                    "properties": {
+                        "data": {
+                            "type": "array",
+                            "description": "Subscriber, delivery status and the id of the message sent, if delivered",
+                            "items": {
+                                "type": "array",
+                                "prefixItems": [
+                                    { "type": "string" },
+                                    { "$ref": "#/components/schemas/Status" },
+                                    { "type": ["string", "null"] },
+                                ],
+                                "minItems": 3,
+                                "maxItems": 3,
+                            },
+                        },
+                    },
+                },
+                "Error": {
+                    "type": "object",
+                    "required": ["error", "code"],
+                    "properties": {
+                        "error": { "type": "string" },
+                        "code": { "type": "integer" },
+                    },
+                },
+            },
+            "responses": {
+                "Error": {
+                    "description": "Request rejected",
+                    "content": {
+                        "application/json": {
+                            "schema": { "$ref": "#/components/schemas/Error" },
+                        },
+                    },
+                },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::handlers::alerts::Alert;
+    use actix_web::{test::TestRequest, web, App};
+
+    #[actix_web::test]
+    async fn it_serves_the_openapi_document() {
+        let app = actix_web::test::init_service(
+            App::new().route("/openapi.json", web::get().to(get_openapi)),
+        )
+        .await;
+        let req = TestRequest::get().uri("/openapi.json").to_request();
+        let document: Value = actix_web::test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(document["openapi"], "3.1.0");
+        assert_eq!(
+            document["components"]["schemas"]["Severity"]["enum"],
+            json!(["high", "medium", "low"])
+        );
+        assert!(document["paths"]["/alerts"]["post"].is_object());
+    }
+
+    #[test]
+    fn it_describes_the_alert_fields() {
+        let document = openapi_document();
+        let schema = &document["components"]["schemas"]["Alert"];
+        // the example must be a valid alert with exactly the described fields
+        let example = schema["example"].clone();
+        let mut fields: Vec<&String> = example.as_object().unwrap().keys().collect();
+        let mut properties: Vec<&String> =
+            schema["properties"].as_object().unwrap().keys().collect();
+        fields.sort();
+        properties.sort();
+        assert_eq!(fields, properties);
+        let alert: Alert = serde_json::from_value(example).unwrap();
+        assert!(alert.validate().is_ok());
+    }
+}
@@ -0,0 +1,89 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::api::helpers::respond_json;
+use crate::cache::{get_conn, CacheKey, Date};
+use crate::errors::{ApiError, CacheError};
+use crate::Abot;
+use actix_web::{web, web::Json};
+use chrono::Utc;
+use redis::aio::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    date: Option<Date>,
+}
+
+impl StatsQuery {
+    /// Returns the requested date (YYMMDD) or today if not specified
+    fn date(&self) -> Result<Date, ApiError> {
+        match &self.date {
+            Some(date) if date.len() == 6 && date.chars().all(|c| c.is_ascii_digit()) => {
+                Ok(date.to_string())
+            }
+            Some(date) => Err(ApiError::BadRequest(format!(
+                "date {} must be formatted as YYMMDD",
+                date
+            ))),
+            None => Ok(Utc::now().format("%y%m%d").to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct CommandStatsResponse {
+    date: Date,
+    commands: BTreeMap<String, u64>,
+}
+
+/// Handler to get how many times each command has been processed in a day
+pub async fn get_command_stats(
+    query: web::Query<StatsQuery>,
+    abot: web::Data<Abot>,
+) -> Result<Json<CommandStatsResponse>, ApiError> {
+    let date = query.date()?;
+    let mut conn = get_conn(&abot.cache).await?;
+
+    let commands = redis::cmd("HGETALL")
+        .arg(CacheKey::CommandStats(date.to_string()))
+        .query_async::<Connection, BTreeMap<String, u64>>(&mut conn)
+        .await
+        .map_err(CacheError::RedisCMDError)?;
+
+    respond_json(CommandStatsResponse { date, commands })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_date_query() {
+        let query = web::Query::<StatsQuery>::from_query("date=240131").unwrap();
+        assert_eq!(query.date().unwrap(), "240131");
+        let query = web::Query::<StatsQuery>::from_query("").unwrap();
+        assert_eq!(query.date().unwrap().len(), 6);
+        let query = web::Query::<StatsQuery>::from_query("date=2024-01-31").unwrap();
+        assert!(query.date().is_err());
+    }
+}
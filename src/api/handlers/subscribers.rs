@@ -0,0 +1,151 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::abot::{normalize_member_id, MemberId, MuteTime, Severity};
+use crate::api::helpers::respond_json;
+use crate::cache::{get_conn, CacheKey};
+use crate::errors::{ApiError, CacheError};
+use crate::matrix::UserID;
+use crate::Abot;
+use actix_web::{web, web::Json};
+use redis::aio::Connection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct SubscribersQuery {
+    severity: Option<Severity>,
+}
+
+impl SubscribersQuery {
+    /// Returns the requested severity or all of them if not specified
+    fn severities(&self) -> Vec<Severity> {
+        match &self.severity {
+            Some(severity) => vec![severity.clone()],
+            None => vec![Severity::High, Severity::Medium, Severity::Low],
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Subscriber {
+    user_id: UserID,
+    severity: Severity,
+    mute: Option<MuteTime>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SubscribersResponse {
+    member_id: MemberId,
+    subscribers: Vec<Subscriber>,
+}
+
+/// Handler to list the users subscribed to the alerts of a member
+pub async fn get_subscribers(
+    member_id: web::Path<MemberId>,
+    query: web::Query<SubscribersQuery>,
+    abot: web::Data<Abot>,
+) -> Result<Json<SubscribersResponse>, ApiError> {
+    let member_id = normalize_member_id(&member_id);
+    let mut conn = get_conn(&abot.cache).await?;
+
+    let mut subscribers: Vec<Subscriber> = Vec::new();
+    for severity in query.severities() {
+        let mut user_ids = redis::cmd("SMEMBERS")
+            .arg(CacheKey::Subscribers(
+                member_id.to_string(),
+                severity.clone(),
+            ))
+            .query_async::<Connection, Vec<UserID>>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+        user_ids.sort();
+
+        for user_id in user_ids {
+            let mute = redis::cmd("HGET")
+                .arg(CacheKey::SubscriberConfig(
+                    user_id.to_string(),
+                    member_id.to_string(),
+                    severity.clone(),
+                ))
+                .arg("mute")
+                .query_async::<Connection, Option<MuteTime>>(&mut conn)
+                .await
+                .map_err(CacheError::RedisCMDError)?;
+
+            subscribers.push(Subscriber {
+                user_id,
+                severity: severity.clone(),
+                mute,
+            });
+        }
+    }
+
+    respond_json(SubscribersResponse {
+        member_id,
+        subscribers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_severity_query() {
+        let query = web::Query::<SubscribersQuery>::from_query("severity=high").unwrap();
+        assert_eq!(query.severities(), vec![Severity::High]);
+        let query = web::Query::<SubscribersQuery>::from_query("").unwrap();
+        assert_eq!(
+            query.severities(),
+            vec![Severity::High, Severity::Medium, Severity::Low]
+        );
+        assert!(web::Query::<SubscribersQuery>::from_query("severity=urgent").is_err());
+    }
+
+    #[test]
+    fn it_serializes_subscribers_response() {
+        let response = SubscribersResponse {
+            member_id: "turboflakes".into(),
+            subscribers: vec![
+                Subscriber {
+                    user_id: "@alice:matrix.org".into(),
+                    severity: Severity::High,
+                    mute: Some(5),
+                },
+                Subscriber {
+                    user_id: "@bob:matrix.org".into(),
+                    severity: Severity::Low,
+                    mute: None,
+                },
+            ],
+        };
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({
+                "member_id": "turboflakes",
+                "subscribers": [
+                    { "user_id": "@alice:matrix.org", "severity": "high", "mute": 5 },
+                    { "user_id": "@bob:matrix.org", "severity": "low", "mute": null }
+                ]
+            })
+        );
+    }
+}
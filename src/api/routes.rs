@@ -21,7 +21,15 @@
 
 use crate::api::guards::ApiKeyGuard;
 use crate::api::handlers::alerts::post_alert;
+use crate::api::handlers::export::{get_export, post_import};
+use crate::api::handlers::health::get_health;
 use crate::api::handlers::index::get_index;
+use crate::api::handlers::members::post_members_refresh;
+use crate::api::handlers::metrics::get_metrics;
+use crate::api::handlers::openapi::get_openapi;
+use crate::api::handlers::stats::get_command_stats;
+use crate::api::handlers::subscribers::get_subscribers;
+use crate::errors::json_error_handler;
 use actix_web::web;
 
 /// All routes are placed here
@@ -29,13 +37,29 @@ pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg
         // Index
         .route("/", web::get().to(get_index))
+        // Prometheus metrics
+        .route("/metrics", web::get().to(get_metrics))
+        // Health of the alert delivery
+        .route("/health", web::get().to(get_health))
+        // OpenAPI description of the alert endpoint
+        .route("/openapi.json", web::get().to(get_openapi))
         // /api/v1 routes
         .service(
             web::scope("/api/v1")
                 .guard(ApiKeyGuard)
+                .app_data(web::JsonConfig::default().error_handler(json_error_handler))
                 // API info
                 .route("", web::get().to(get_index))
                 // Alerts route
-                .route("/alerts", web::post().to(post_alert)),
+                .route("/alerts", web::post().to(post_alert))
+                // Members route
+                .route("/members/refresh", web::post().to(post_members_refresh))
+                // Subscribers route
+                .route("/subscribers/{member_id}", web::get().to(get_subscribers))
+                // Stats routes
+                .route("/stats/commands", web::get().to(get_command_stats))
+                // Export/Import routes
+                .route("/export", web::get().to(get_export))
+                .route("/import", web::post().to(post_import)),
         );
 }
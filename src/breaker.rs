@@ -0,0 +1,137 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    failures: u32,
+    opened_at: Option<Instant>,
+    trial: bool,
+}
+
+/// Stops attempting requests to a failing service for a cooldown period after a number
+/// of consecutive failures. The state is shared between clones.
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Arc<Mutex<BreakerState>>,
+}
+
+impl CircuitBreaker {
+    /// Returns a circuit breaker that opens after threshold consecutive failures,
+    /// disabled when threshold is 0
+    pub fn new(threshold: u32, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            threshold,
+            cooldown,
+            state: Arc::new(Mutex::new(BreakerState::default())),
+        }
+    }
+
+    /// Returns true if a request may be attempted. Once the cooldown has elapsed a single
+    /// trial request is let through, the others are blocked until the trial reports its
+    /// success (the breaker closes) or its failure (the breaker opens again)
+    pub fn allows(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().expect("breaker lock");
+        match state.opened_at {
+            Some(opened_at) => {
+                if state.trial || now.saturating_duration_since(opened_at) < self.cooldown {
+                    return false;
+                }
+                state.trial = true;
+                true
+            }
+            None => true,
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().expect("breaker lock");
+        state.failures = 0;
+        state.opened_at = None;
+        state.trial = false;
+    }
+
+    pub fn record_failure(&self, now: Instant) {
+        let mut state = self.state.lock().expect("breaker lock");
+        state.failures = state.failures.saturating_add(1);
+        state.trial = false;
+        if self.threshold > 0 && state.failures >= self.threshold {
+            state.opened_at = Some(now);
+        }
+    }
+
+    /// Returns true while the service is considered degraded
+    pub fn is_open(&self) -> bool {
+        self.state.lock().expect("breaker lock").opened_at.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_opens_after_consecutive_failures_and_closes_on_success() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+        breaker.record_failure(now);
+        assert!(breaker.allows(now));
+        breaker.record_failure(now);
+        assert!(breaker.is_open());
+        assert!(!breaker.allows(now + Duration::from_secs(30)));
+        // a trial request is let through after the cooldown
+        assert!(breaker.allows(now + Duration::from_secs(60)));
+        assert!(!breaker.allows(now + Duration::from_secs(60)));
+        breaker.record_success();
+        assert!(!breaker.is_open());
+        assert!(breaker.allows(now));
+    }
+
+    #[test]
+    fn it_lets_a_single_trial_through_after_the_cooldown() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+        breaker.record_failure(now);
+        let after_cooldown = now + Duration::from_secs(60);
+        assert!(breaker.allows(after_cooldown));
+        assert!(!breaker.allows(after_cooldown));
+        // the failed trial opens the breaker for another cooldown
+        breaker.record_failure(after_cooldown);
+        assert!(breaker.is_open());
+        assert!(!breaker.allows(after_cooldown + Duration::from_secs(30)));
+        assert!(breaker.allows(after_cooldown + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn it_never_opens_when_disabled() {
+        let breaker = CircuitBreaker::new(0, Duration::from_secs(60));
+        let now = Instant::now();
+        for _ in 0..10 {
+            breaker.record_failure(now);
+        }
+        assert!(!breaker.is_open());
+        assert!(breaker.allows(now));
+    }
+}
@@ -0,0 +1,201 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::abot::{MemberId, ServiceId, Severity};
+use crate::matrix::UserID;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// CoalescedAlert is an alert buffered during the coalescing window
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoalescedAlert {
+    pub code: u32,
+    pub severity: Severity,
+    pub service_id: ServiceId,
+    pub message: String,
+    pub formatted_message: String,
+}
+
+// Buffers are keyed by subscriber and member, with the time their window opened
+type Buffers = HashMap<(UserID, MemberId), (Instant, Vec<CoalescedAlert>)>;
+
+// Alerts buffered for a subscriber and member, ready to be delivered
+pub type CoalescedBuffer = (UserID, MemberId, Vec<CoalescedAlert>);
+
+/// In-memory buffer of the alerts to be delivered by subscriber and member
+#[derive(Clone, Default)]
+pub struct Coalescer {
+    buffers: Arc<Mutex<Buffers>>,
+}
+
+impl Coalescer {
+    /// Buffers the alert, returns true if it is the first alert of the window
+    /// and the delivery of the buffer should be scheduled
+    pub fn push(&self, subscriber: &str, member_id: &str, alert: CoalescedAlert) -> bool {
+        self.push_at(subscriber, member_id, alert, Instant::now())
+    }
+
+    fn push_at(
+        &self,
+        subscriber: &str,
+        member_id: &str,
+        alert: CoalescedAlert,
+        now: Instant,
+    ) -> bool {
+        let mut buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        let (_, buffer) = buffers
+            .entry((subscriber.to_string(), member_id.to_string()))
+            .or_insert_with(|| (now, Vec::new()));
+        buffer.push(alert);
+        buffer.len() == 1
+    }
+
+    /// Removes and returns the buffers whose window is over
+    pub fn take_due(&self, window: Duration, now: Instant) -> Vec<CoalescedBuffer> {
+        let mut buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        let due: Vec<(UserID, MemberId)> = buffers
+            .iter()
+            .filter(|(_, (opened, _))| now.saturating_duration_since(*opened) >= window)
+            .map(|(key, _)| key.clone())
+            .collect();
+        due.into_iter()
+            .filter_map(|key| {
+                let (_, alerts) = buffers.remove(&key)?;
+                Some((key.0, key.1, alerts))
+            })
+            .collect()
+    }
+
+    /// Removes and returns all the buffers, used to flush them on shutdown
+    pub fn take_all(&self) -> Vec<CoalescedBuffer> {
+        let mut buffers = self.buffers.lock().unwrap_or_else(|e| e.into_inner());
+        buffers
+            .drain()
+            .map(|((subscriber, member_id), (_, alerts))| (subscriber, member_id, alerts))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::digest_report;
+
+    fn alert(code: u32, service_id: &str) -> CoalescedAlert {
+        CoalescedAlert {
+            code,
+            severity: Severity::High,
+            service_id: service_id.into(),
+            message: format!("alert {}", code),
+            formatted_message: format!("<b>alert {}</b>", code),
+        }
+    }
+
+    #[test]
+    fn it_coalesces_alerts_within_the_window_into_one_digest() {
+        let coalescer = Coalescer::default();
+        assert!(coalescer.push(
+            "@alice:matrix.org",
+            "turboflakes",
+            alert(100, "polkadot-rpc")
+        ));
+        assert!(!coalescer.push("@alice:matrix.org", "turboflakes", alert(101, "kusama-rpc")));
+        assert!(!coalescer.push(
+            "@alice:matrix.org",
+            "turboflakes",
+            alert(102, "westend-rpc")
+        ));
+        // other subscribers and members have their own buffer
+        assert!(coalescer.push("@bob:matrix.org", "turboflakes", alert(100, "polkadot-rpc")));
+        assert!(coalescer.push("@alice:matrix.org", "amforc", alert(100, "polkadot-rpc")));
+
+        let buffers = coalescer.take_all();
+        assert_eq!(buffers.len(), 3);
+        let (_, _, alerts) = buffers
+            .into_iter()
+            .find(|(subscriber, member_id, _)| {
+                subscriber == "@alice:matrix.org" && member_id == "turboflakes"
+            })
+            .unwrap();
+        assert_eq!(alerts.len(), 3);
+        assert!(coalescer.take_all().is_empty());
+
+        let digest = digest_report("turboflakes", &alerts);
+        let formatted = digest.formatted_message();
+        assert!(formatted.starts_with("🚨 <b>3 alerts from turboflakes</b>"));
+        assert!(formatted.contains("[100 — RPC service offline] ― polkadot-rpc high"));
+        assert!(formatted.contains("[101 — Chain halted] ― kusama-rpc high"));
+        assert!(formatted.contains("[102 — Chain out of sync] ― westend-rpc high"));
+        // the plain body carries no markup
+        assert!(digest
+            .message()
+            .starts_with("🚨 3 alerts from turboflakes:\n[100 — RPC service offline]"));
+        assert!(!digest.message().contains('<'));
+
+        // a new window starts once the buffer is taken
+        assert!(coalescer.push(
+            "@alice:matrix.org",
+            "turboflakes",
+            alert(103, "polkadot-rpc")
+        ));
+    }
+
+    #[test]
+    fn it_takes_the_buffers_once_their_window_is_over() {
+        let coalescer = Coalescer::default();
+        let window = Duration::from_secs(60);
+        let start = Instant::now();
+        coalescer.push_at(
+            "@alice:matrix.org",
+            "turboflakes",
+            alert(100, "polkadot-rpc"),
+            start,
+        );
+        coalescer.push_at(
+            "@bob:matrix.org",
+            "turboflakes",
+            alert(100, "polkadot-rpc"),
+            start + Duration::from_secs(30),
+        );
+        // alerts pushed later join the window already open
+        coalescer.push_at(
+            "@alice:matrix.org",
+            "turboflakes",
+            alert(101, "kusama-rpc"),
+            start + Duration::from_secs(50),
+        );
+
+        assert!(coalescer
+            .take_due(window, start + Duration::from_secs(59))
+            .is_empty());
+        let due = coalescer.take_due(window, start + Duration::from_secs(60));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0, "@alice:matrix.org");
+        assert_eq!(due[0].2.len(), 2);
+
+        // on shutdown the buffers are flushed regardless of their window
+        let flushed = coalescer.take_all();
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].0, "@bob:matrix.org");
+        assert!(coalescer.take_all().is_empty());
+    }
+}
@@ -30,8 +30,9 @@
 //
 // Set Config struct into a CONFIG lazy_static to avoid multiple processing.
 //
+use crate::abot::{MuteTime, Severity};
+use crate::matrix::{is_valid_matrix_id, is_valid_user_id, MsgType};
 use clap::{App, Arg};
-use dotenv;
 use lazy_static::lazy_static;
 use log::info;
 use serde::Deserialize;
@@ -47,11 +48,131 @@ fn default_mute_time() -> u32 {
     5
 }
 
+/// provides default value (minutes) for pause_time if ABOT_PAUSE_TIME env var is not set
+fn default_pause_time() -> u32 {
+    1440
+}
+
+/// provides default value (bytes) for report_max_size if ABOT_REPORT_MAX_SIZE env var is not set
+fn default_report_max_size() -> usize {
+    16384
+}
+
+/// provides default value (bytes) for max_alert_body_bytes if ABOT_MAX_ALERT_BODY_BYTES env var is not set
+fn default_max_alert_body_bytes() -> usize {
+    262144
+}
+
+/// provides default value (seconds) for command_poll_interval_secs if ABOT_COMMAND_POLL_INTERVAL_SECS env var is not set
+fn default_command_poll_interval_secs() -> u64 {
+    6
+}
+
+/// provides default value (seconds) for command_max_age_secs if ABOT_COMMAND_MAX_AGE_SECS env var is not set
+fn default_command_max_age_secs() -> u64 {
+    3600
+}
+
+/// provides default value for matrix_homeserver_url if ABOT_MATRIX_HOMESERVER_URL env var is not set
+fn default_matrix_homeserver_url() -> String {
+    "https://matrix.org".into()
+}
+
+/// provides default value (seconds) for http_timeout_secs if ABOT_HTTP_TIMEOUT_SECS env var is not set
+fn default_http_timeout_secs() -> u64 {
+    30
+}
+
+/// provides default value (seconds) for command_rate_limit_half_life_secs if ABOT_COMMAND_RATE_LIMIT_HALF_LIFE_SECS env var is not set
+fn default_command_rate_limit_half_life_secs() -> u64 {
+    30
+}
+
+/// provides default value (severities) for alert_callout_severities if ABOT_ALERT_CALLOUT_SEVERITIES env var is not set
+fn default_alert_callout_severities() -> Vec<Severity> {
+    vec![Severity::High]
+}
+
+/// provides default value (UTC) for digest_time if ABOT_DIGEST_TIME env var is not set
+fn default_digest_time() -> String {
+    "08:00".into()
+}
+
+/// provides default value for matrix_bot_name if ABOT_MATRIX_BOT_NAME env var is not set
+fn default_matrix_bot_name() -> String {
+    "IBP ALERTS".into()
+}
+
+/// provides default value for matrix_private_room_name if ABOT_MATRIX_PRIVATE_ROOM_NAME env var is not set
+fn default_matrix_private_room_name() -> String {
+    "{name} Bot (Private)".into()
+}
+
+/// provides default value for matrix_private_room_topic if ABOT_MATRIX_PRIVATE_ROOM_TOPIC env var is not set
+fn default_matrix_private_room_topic() -> String {
+    "{name} Bot".into()
+}
+
+/// provides default value for matrix_auto_create_rooms if ABOT_MATRIX_AUTO_CREATE_ROOMS env var is not set
+fn default_matrix_auto_create_rooms() -> bool {
+    true
+}
+
+/// provides default value for matrix_circuit_breaker_threshold if ABOT_MATRIX_CIRCUIT_BREAKER_THRESHOLD env var is not set
+fn default_matrix_circuit_breaker_threshold() -> u32 {
+    5
+}
+
+/// provides default value (seconds) for matrix_circuit_breaker_cooldown_secs if ABOT_MATRIX_CIRCUIT_BREAKER_COOLDOWN_SECS env var is not set
+fn default_matrix_circuit_breaker_cooldown_secs() -> u64 {
+    60
+}
+
+/// provides default value (minutes) for private_room_invite_grace_period if ABOT_PRIVATE_ROOM_INVITE_GRACE_PERIOD env var is not set
+fn default_private_room_invite_grace_period() -> u32 {
+    1440
+}
+
 /// provides default value (minutes) for error interval if ABOT_ERROR_INTERVAL env var is not set
 fn default_error_interval() -> u64 {
     30
 }
 
+/// provides default value (percentage) for error_interval_jitter if ABOT_ERROR_INTERVAL_JITTER env var is not set
+fn default_error_interval_jitter() -> u32 {
+    10
+}
+
+/// provides default value for command_prefix if ABOT_COMMAND_PREFIX env var is not set
+fn default_command_prefix() -> String {
+    "!".into()
+}
+
+/// provides default value for severity_emoji_high if ABOT_SEVERITY_EMOJI_HIGH env var is not set
+fn default_severity_emoji_high() -> String {
+    "🔥🔥🔥".into()
+}
+
+/// provides default value for severity_emoji_medium if ABOT_SEVERITY_EMOJI_MEDIUM env var is not set
+fn default_severity_emoji_medium() -> String {
+    "🔥🔥".into()
+}
+
+/// provides default value for severity_emoji_low if ABOT_SEVERITY_EMOJI_LOW env var is not set
+fn default_severity_emoji_low() -> String {
+    "🔥".into()
+}
+
+/// provides default value (occurrences) for alert_escalation_medium if ABOT_ALERT_ESCALATION_MEDIUM env var is not set
+fn default_alert_escalation_medium() -> u32 {
+    3
+}
+
+/// provides default value (occurrences) for alert_escalation_high if ABOT_ALERT_ESCALATION_HIGH env var is not set
+fn default_alert_escalation_high() -> u32 {
+    6
+}
+
 /// provides default value for data_path if ABOT_DATA_PATH env var is not set
 fn default_data_path() -> String {
     "./".into()
@@ -67,6 +188,11 @@ fn default_api_port() -> u16 {
     5010
 }
 
+/// provides default value for api_key_header if ABOT_API_KEY_HEADER env var is not set
+fn default_api_key_header() -> String {
+    "X-API-KEY".into()
+}
+
 /// provides default value for api_port if ONET_API_PORT env var is not set
 fn default_api_cors_allow_origin() -> String {
     "*".into()
@@ -82,35 +208,159 @@ fn default_redis_database() -> u8 {
     0
 }
 
+#[derive(Clone, Deserialize, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Clone, Deserialize, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertDedupKey {
+    #[default]
+    Code,
+    Content,
+}
+
 #[derive(Clone, Deserialize, Debug)]
 pub struct Config {
     // general configuration
     pub api_keys: Vec<String>,
+    #[serde(default = "default_api_key_header")]
+    pub api_key_header: String,
     #[serde(default)]
     pub members_json_url: String,
     #[serde(default)]
+    pub members_refresh_interval_secs: u64,
+    #[serde(default)]
     pub ibp_monitor_url: String,
+    #[serde(default)]
+    pub member_links: Vec<String>,
     #[serde(default = "default_mute_time")]
     pub mute_time: u32,
+    #[serde(default)]
+    pub mute_time_high: Option<u32>,
+    #[serde(default)]
+    pub mute_time_medium: Option<u32>,
+    #[serde(default)]
+    pub mute_time_low: Option<u32>,
+    #[serde(default = "default_pause_time")]
+    pub pause_time: u32,
+    #[serde(default)]
+    pub alert_dedup_key: AlertDedupKey,
+    #[serde(default = "default_severity_emoji_high")]
+    pub severity_emoji_high: String,
+    #[serde(default = "default_severity_emoji_medium")]
+    pub severity_emoji_medium: String,
+    #[serde(default = "default_severity_emoji_low")]
+    pub severity_emoji_low: String,
+    #[serde(default = "default_report_max_size")]
+    pub report_max_size: usize,
+    #[serde(default)]
+    pub alert_latency_threshold_ms: u64,
+    #[serde(default)]
+    pub alert_coalesce_window_secs: u64,
+    #[serde(default)]
+    pub alert_thread_window_secs: u64,
+    #[serde(default)]
+    pub alert_escalation_window_secs: u64,
+    #[serde(default = "default_alert_escalation_medium")]
+    pub alert_escalation_medium: u32,
+    #[serde(default = "default_alert_escalation_high")]
+    pub alert_escalation_high: u32,
     #[serde(default = "default_error_interval")]
     pub error_interval: u64,
+    #[serde(default = "default_error_interval_jitter")]
+    pub error_interval_jitter: u32,
     #[serde(default)]
     pub is_debug: bool,
     #[serde(default = "default_data_path")]
     pub data_path: String,
+    #[serde(default)]
+    pub log_format: LogFormat,
     // matrix configuration
+    #[serde(default = "default_command_prefix")]
+    pub command_prefix: String,
+    #[serde(default)]
+    pub admin_user_ids: Vec<String>,
+    #[serde(default = "default_command_poll_interval_secs")]
+    pub command_poll_interval_secs: u64,
+    #[serde(default = "default_command_max_age_secs")]
+    pub command_max_age_secs: u64,
+    #[serde(default)]
+    pub command_rate_limit_burst: u32,
+    #[serde(default = "default_command_rate_limit_half_life_secs")]
+    pub command_rate_limit_half_life_secs: u64,
+    #[serde(default)]
+    pub matrix_sync_timeout_ms: u64,
+    #[serde(default = "default_matrix_homeserver_url")]
+    pub matrix_homeserver_url: String,
     #[serde(default)]
     pub matrix_public_room: String,
     #[serde(default)]
+    pub matrix_public_rooms: Vec<String>,
+    #[serde(default)]
     pub matrix_bot_user: String,
     #[serde(default)]
     pub matrix_bot_password: String,
     #[serde(default)]
     pub matrix_disabled: bool,
     #[serde(default)]
+    pub matrix_dry_run: bool,
+    #[serde(default)]
     pub matrix_public_room_disabled: bool,
     #[serde(default)]
+    pub matrix_callout_rooms: Vec<String>,
+    #[serde(default = "default_alert_callout_severities")]
+    pub alert_callout_severities: Vec<Severity>,
+    #[serde(default = "default_private_room_invite_grace_period")]
+    pub private_room_invite_grace_period: u32,
+    #[serde(default)]
+    pub startup_announcement: bool,
+    #[serde(default)]
+    pub new_members_announcement: bool,
+    #[serde(default)]
+    pub not_whitelisted_notice: bool,
+    #[serde(default = "default_digest_time")]
+    pub digest_time: String,
+    #[serde(default = "default_matrix_bot_name")]
+    pub matrix_bot_name: String,
+    #[serde(default = "default_matrix_private_room_name")]
+    pub matrix_private_room_name: String,
+    #[serde(default = "default_matrix_private_room_topic")]
+    pub matrix_private_room_topic: String,
+    #[serde(default)]
     pub matrix_bot_display_name_disabled: bool,
+    #[serde(default)]
+    pub matrix_update_topic: bool,
+    #[serde(default)]
+    pub matrix_private_msgtype: MsgType,
+    #[serde(default)]
+    pub matrix_public_msgtype: MsgType,
+    #[serde(default = "default_matrix_auto_create_rooms")]
+    pub matrix_auto_create_rooms: bool,
+    #[serde(default)]
+    pub matrix_provision_invited_members: bool,
+    #[serde(default)]
+    pub matrix_private_rooms_per_cycle: usize,
+    #[serde(default = "default_matrix_circuit_breaker_threshold")]
+    pub matrix_circuit_breaker_threshold: u32,
+    #[serde(default = "default_matrix_circuit_breaker_cooldown_secs")]
+    pub matrix_circuit_breaker_cooldown_secs: u64,
+    #[serde(default)]
+    pub send_test_message: String,
+    // telegram configuration
+    #[serde(default)]
+    pub telegram_enabled: bool,
+    #[serde(default)]
+    pub telegram_bot_token: String,
+    #[serde(default)]
+    pub telegram_chat_id: String,
+    // outbound webhook configuration
+    #[serde(default)]
+    pub outbound_webhook_url: String,
     // api
     #[serde(default = "default_api_host")]
     pub api_host: String,
@@ -118,15 +368,119 @@ pub struct Config {
     pub api_port: u16,
     #[serde(default = "default_api_cors_allow_origin")]
     pub api_cors_allow_origin: String,
+    #[serde(default)]
+    pub api_unix_socket: String,
+    #[serde(default)]
+    pub alert_hmac_secret: String,
+    #[serde(default = "default_max_alert_body_bytes")]
+    pub max_alert_body_bytes: usize,
+    #[serde(default)]
+    pub alert_freshness_window_secs: u64,
+    #[serde(default = "default_http_timeout_secs")]
+    pub http_timeout_secs: u64,
     // redis configuration
     #[serde(default = "default_redis_host")]
     pub redis_hostname: String,
     #[serde(default)]
+    pub redis_username: String,
+    #[serde(default)]
     pub redis_password: String,
     #[serde(default = "default_redis_database")]
     pub redis_database: u8,
 }
 
+impl Config {
+    /// Returns the default mute time (minutes) for new subscriptions of the given severity,
+    /// falling back to mute_time when no severity specific value is set
+    pub fn mute_time_for(&self, severity: &Severity) -> MuteTime {
+        match severity {
+            Severity::High => self.mute_time_high,
+            Severity::Medium => self.mute_time_medium,
+            Severity::Low => self.mute_time_low,
+        }
+        .unwrap_or(self.mute_time)
+    }
+
+    /// Returns true if alerts of the given severity are also called out in the callout rooms,
+    /// alerts are always delivered to the subscribers private rooms
+    pub fn is_callout_severity(&self, severity: &Severity) -> bool {
+        self.alert_callout_severities.contains(severity)
+    }
+
+    /// Returns the public rooms where alerts are broadcast, the first one being the room
+    /// where members and commands are loaded from. Falls back to matrix_public_room when
+    /// matrix_public_rooms is not set
+    pub fn public_rooms(&self) -> Vec<String> {
+        let mut rooms: Vec<String> = Vec::new();
+        for room in self.matrix_public_rooms.iter().map(|room| room.trim()) {
+            if !room.is_empty() && !rooms.iter().any(|r| r == room) {
+                rooms.push(room.to_string());
+            }
+        }
+        if rooms.is_empty() {
+            rooms.push(self.matrix_public_room.clone());
+        }
+        rooms
+    }
+
+    /// Verifies the configuration required to run the bot, returning an actionable message otherwise
+    pub fn validate(&self) -> Result<(), String> {
+        if self.matrix_disabled {
+            return Ok(());
+        }
+        if !is_valid_user_id(&self.matrix_bot_user) {
+            return Err(format!(
+                "ABOT_MATRIX_BOT_USER '{}' must be a matrix user id like '@your-own-bot-account:matrix.org'",
+                self.matrix_bot_user
+            ));
+        }
+        if self.matrix_bot_password.is_empty() {
+            return Err("ABOT_MATRIX_BOT_PASSWORD must be defined".to_string());
+        }
+        if self.command_prefix.trim().is_empty() {
+            return Err("ABOT_COMMAND_PREFIX must not be empty".to_string());
+        }
+        if !self.matrix_public_room_disabled {
+            let var = if self.matrix_public_rooms.is_empty() {
+                "ABOT_MATRIX_PUBLIC_ROOM"
+            } else {
+                "ABOT_MATRIX_PUBLIC_ROOMS"
+            };
+            if let Some(room) = self
+                .public_rooms()
+                .into_iter()
+                .find(|room| !is_valid_room_alias(room))
+            {
+                return Err(format!(
+                    "{} '{}' must be a room alias like 'ibp-alerts:matrix.org'",
+                    var, room
+                ));
+            }
+        }
+        if let Some(room) = self
+            .matrix_callout_rooms
+            .iter()
+            .find(|room| !is_valid_room_alias(room))
+        {
+            return Err(format!(
+                "ABOT_MATRIX_CALLOUT_ROOMS '{}' must be a room alias like 'ibp-callouts:matrix.org'",
+                room
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if the room alias is 'name:server' with an optional leading '#'
+fn is_valid_room_alias(alias: &str) -> bool {
+    let alias = alias.trim();
+    if alias.starts_with('#') {
+        is_valid_matrix_id(alias, '#')
+    } else {
+        is_valid_matrix_id(&format!("#{}", alias), '#')
+    }
+}
+
 /// Inject dotenv and env vars into the Config struct
 fn get_config() -> Config {
     // Define CLI flags with clap
@@ -175,6 +529,18 @@ fn get_config() -> Config {
         Arg::with_name("debug")
           .long("debug")
           .help("Prints debug information verbosely."))
+    .arg(
+      Arg::with_name("log-format")
+        .long("log-format")
+        .takes_value(true)
+        .possible_values(&["text", "json"])
+        .help("Log output format. Use 'json' to emit one JSON object per line for log aggregators (default: text)."))
+    .arg(
+      Arg::with_name("send-test-message")
+        .long("send-test-message")
+        .takes_value(true)
+        .value_name("USER_ID")
+        .help("Authenticate the matrix bot user, send a test message to the given matrix user (e.g. '@alice:matrix.org') and exit without starting the API server."))
     .arg(
       Arg::with_name("config-path")
         .short("c")
@@ -191,11 +557,11 @@ fn get_config() -> Config {
     // Try to load configuration from file first
     let config_path = matches.value_of("config-path").unwrap_or(".env");
 
-    match dotenv::from_filename(&config_path).ok() {
+    match dotenv::from_filename(config_path).ok() {
         Some(_) => info!("Loading configuration from {} file", &config_path),
         None => {
             let config_path = env::var("ABOT_CONFIG_FILENAME").unwrap_or(".env".to_string());
-            if let Some(_) = dotenv::from_filename(&config_path).ok() {
+            if dotenv::from_filename(&config_path).is_ok() {
                 info!("Loading configuration from {} file", &config_path);
             }
         }
@@ -213,6 +579,10 @@ fn get_config() -> Config {
         env::set_var("ABOT_IS_DEBUG", "true");
     }
 
+    if let Some(log_format) = matches.value_of("log-format") {
+        env::set_var("ABOT_LOG_FORMAT", log_format);
+    }
+
     if let Some(data_path) = matches.value_of("data-path") {
         env::set_var("ABOT_DATA_PATH", data_path);
     }
@@ -221,6 +591,10 @@ fn get_config() -> Config {
         env::set_var("ABOT_MATRIX_DISABLED", "true");
     }
 
+    if matches.is_present("disable-matrix-bot-display-name") {
+        env::set_var("ABOT_MATRIX_BOT_DISPLAY_NAME_DISABLED", "true");
+    }
+
     if let Some(matrix_bot_user) = matches.value_of("matrix-bot-user") {
         env::set_var("ABOT_MATRIX_BOT_USER", matrix_bot_user);
     }
@@ -229,6 +603,10 @@ fn get_config() -> Config {
         env::set_var("ABOT_MATRIX_BOT_PASSWORD", matrix_bot_password);
     }
 
+    if let Some(user_id) = matches.value_of("send-test-message") {
+        env::set_var("ABOT_SEND_TEST_MESSAGE", user_id);
+    }
+
     if let Some(error_interval) = matches.value_of("error-interval") {
         env::set_var("ABOT_ERROR_INTERVAL", error_interval);
     }
@@ -254,4 +632,145 @@ mod tests {
         let config = &CONFIG;
         assert_ne!(config.data_path, "".to_string());
     }
+
+    fn config(vars: Vec<(&str, &str)>) -> Config {
+        let mut vars: Vec<(String, String)> = vars
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        vars.push(("ABOT_API_KEYS".to_string(), "test".to_string()));
+        envy::prefixed("ABOT_").from_iter(vars).unwrap()
+    }
+
+    #[test]
+    fn it_gets_the_mute_time_by_severity() {
+        let config = config(vec![
+            ("ABOT_MUTE_TIME", "10"),
+            ("ABOT_MUTE_TIME_HIGH", "2"),
+            ("ABOT_MUTE_TIME_LOW", "60"),
+        ]);
+        assert_eq!(config.mute_time_for(&Severity::High), 2);
+        assert_eq!(config.mute_time_for(&Severity::Medium), 10);
+        assert_eq!(config.mute_time_for(&Severity::Low), 60);
+    }
+
+    fn matrix_config(user: &str) -> Config {
+        config(vec![
+            ("ABOT_MATRIX_BOT_USER", user),
+            ("ABOT_MATRIX_BOT_PASSWORD", "password"),
+            ("ABOT_MATRIX_PUBLIC_ROOM", "ibp-alerts:matrix.org"),
+        ])
+    }
+
+    #[test]
+    fn it_validates_a_matrix_bot_user() {
+        assert_eq!(
+            matrix_config("@ibp-alerts-bot:matrix.org").validate(),
+            Ok(())
+        );
+        for user in [
+            "",
+            "ibp-alerts-bot:matrix.org",
+            "@ibp-alerts-bot",
+            "@ibp-alerts-bot:",
+            "@:matrix.org",
+            "@ibp alerts bot:matrix.org",
+        ] {
+            assert!(matrix_config(user).validate().is_err(), "{}", user);
+        }
+    }
+
+    #[test]
+    fn it_validates_the_matrix_rooms_and_password() {
+        let config = config(vec![
+            ("ABOT_MATRIX_BOT_USER", "@ibp-alerts-bot:matrix.org"),
+            ("ABOT_MATRIX_PUBLIC_ROOM", "ibp-alerts:matrix.org"),
+        ]);
+        assert!(config.validate().unwrap_err().contains("PASSWORD"));
+
+        let mut config = matrix_config("@ibp-alerts-bot:matrix.org");
+        config.matrix_public_room = "#ibp-alerts:matrix.org".into();
+        assert_eq!(config.validate(), Ok(()));
+        config.matrix_public_room = "ibp-alerts".into();
+        assert!(config.validate().unwrap_err().contains("PUBLIC_ROOM"));
+        config.matrix_public_room_disabled = true;
+        assert_eq!(config.validate(), Ok(()));
+        config.matrix_callout_rooms = vec!["ibp-callouts".into()];
+        assert!(config.validate().unwrap_err().contains("CALLOUT_ROOMS"));
+    }
+
+    #[test]
+    fn it_rejects_an_empty_command_prefix() {
+        let mut config = matrix_config("@ibp-alerts-bot:matrix.org");
+        config.command_prefix = "ibp:".into();
+        assert_eq!(config.validate(), Ok(()));
+        for prefix in ["", " "] {
+            config.command_prefix = prefix.into();
+            assert!(config.validate().unwrap_err().contains("COMMAND_PREFIX"));
+        }
+    }
+
+    #[test]
+    fn it_routes_alerts_to_the_callout_rooms_by_severity() {
+        let severities = [Severity::High, Severity::Medium, Severity::Low];
+        let cases = vec![
+            (None, [true, false, false]),
+            (Some("high"), [true, false, false]),
+            (Some("high,medium"), [true, true, false]),
+            (Some("low"), [false, false, true]),
+        ];
+        for (value, expected) in cases {
+            let config = match value {
+                Some(value) => config(vec![("ABOT_ALERT_CALLOUT_SEVERITIES", value)]),
+                None => config(vec![]),
+            };
+            let routed: Vec<bool> = severities
+                .iter()
+                .map(|severity| config.is_callout_severity(severity))
+                .collect();
+            assert_eq!(routed, expected, "{:?}", value);
+        }
+        assert!(envy::prefixed("ABOT_")
+            .from_iter::<_, Config>(vec![
+                ("ABOT_API_KEYS".to_string(), "test".to_string()),
+                (
+                    "ABOT_ALERT_CALLOUT_SEVERITIES".to_string(),
+                    "critical".to_string()
+                ),
+            ])
+            .is_err());
+    }
+
+    #[test]
+    fn it_resolves_the_public_rooms() {
+        assert_eq!(
+            matrix_config("@ibp-alerts-bot:matrix.org").public_rooms(),
+            vec!["ibp-alerts:matrix.org"]
+        );
+
+        let mut config = config(vec![
+            ("ABOT_MATRIX_BOT_USER", "@ibp-alerts-bot:matrix.org"),
+            ("ABOT_MATRIX_BOT_PASSWORD", "password"),
+            (
+                "ABOT_MATRIX_PUBLIC_ROOMS",
+                "ibp-alerts:matrix.org, kusama-alerts:matrix.org,ibp-alerts:matrix.org",
+            ),
+        ]);
+        assert_eq!(
+            config.public_rooms(),
+            vec!["ibp-alerts:matrix.org", "kusama-alerts:matrix.org"]
+        );
+        assert_eq!(config.validate(), Ok(()));
+        config.matrix_public_rooms.push("kusama".into());
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .contains("PUBLIC_ROOMS 'kusama'"));
+    }
+
+    #[test]
+    fn it_skips_validation_when_matrix_is_disabled() {
+        let config = config(vec![("ABOT_MATRIX_DISABLED", "true")]);
+        assert_eq!(config.validate(), Ok(()));
+    }
 }
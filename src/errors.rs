@@ -19,9 +19,12 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use actix_web::{error::ResponseError, HttpResponse};
+use actix_web::{
+    error::{JsonPayloadError, ResponseError},
+    http::StatusCode,
+    HttpRequest, HttpResponse,
+};
 use derive_more::Display;
-use reqwest;
 use serde::{Deserialize, Serialize};
 use std::{num::ParseIntError, str::Utf8Error, string::String};
 use thiserror::Error;
@@ -35,6 +38,10 @@ pub enum AbotError {
     Utf8Error(#[from] Utf8Error),
     #[error("Matrix error: {0}")]
     MatrixError(String),
+    #[error("Telegram error: {0}")]
+    TelegramError(String),
+    #[error("Webhook error: {0}")]
+    WebhookError(String),
     #[error("Subscription finished")]
     SubscriptionFinished,
     #[error("Reqwest error: {0}")]
@@ -78,6 +85,8 @@ pub enum MatrixError {
     CacheError(#[from] CacheError),
     #[error("ParseInt error: {0}")]
     ParseIntError(#[from] ParseIntError),
+    #[error("Matrix homeserver degraded")]
+    Degraded,
     #[error("{0}")]
     Other(String),
 }
@@ -99,28 +108,30 @@ impl From<MatrixError> for AbotError {
 #[allow(dead_code)]
 #[derive(Error, Debug, Display, PartialEq)]
 pub enum ApiError {
-    #[allow(dead_code)]
     BadRequest(String),
+    Unauthorized(String),
     NotFound(String),
+    PayloadTooLarge(String),
     InternalServerError(String),
+    ServiceUnavailable(String),
 }
 
 /// Automatically convert ApiErrors to external Response Errors
 impl ResponseError for ApiError {
-    fn error_response(&self) -> HttpResponse {
+    fn status_code(&self) -> StatusCode {
         match self {
-            ApiError::BadRequest(error) => {
-                // let error_resp: ErrorResponse = error.into();
-                HttpResponse::BadRequest().json(ErrorResponse::from(error))
-            }
-            ApiError::NotFound(message) => {
-                HttpResponse::NotFound().json(ErrorResponse::from(message))
-            }
-            ApiError::InternalServerError(error) => {
-                HttpResponse::InternalServerError().json(ErrorResponse::from(error))
-            }
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::InternalServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorResponse::from(self))
+    }
 }
 
 impl From<&str> for ApiError {
@@ -130,17 +141,29 @@ impl From<&str> for ApiError {
 }
 
 /// User-friendly error messages
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct ErrorResponse {
-    errors: Vec<String>,
+    error: String,
+    code: u16,
 }
 
-/// Utility to make transforming a string reference into an ErrorResponse
-impl From<&String> for ErrorResponse {
-    fn from(error: &String) -> Self {
+/// Utility to make transforming an ApiError reference into an ErrorResponse
+impl From<&ApiError> for ErrorResponse {
+    fn from(error: &ApiError) -> Self {
         ErrorResponse {
-            errors: vec![error.into()],
+            error: error.to_string(),
+            code: error.status_code().as_u16(),
+        }
+    }
+}
+
+/// Convert oversized json payloads into a PayloadTooLarge ApiError and malformed ones into a BadRequest ApiError
+pub fn json_error_handler(error: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    match error {
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            ApiError::PayloadTooLarge(error.to_string()).into()
         }
+        _ => ApiError::BadRequest(error.to_string()).into(),
     }
 }
 
@@ -185,7 +208,27 @@ impl From<CacheError> for String {
 /// Convert CacheError to ApiErrors
 impl From<CacheError> for ApiError {
     fn from(error: CacheError) -> Self {
-        ApiError::InternalServerError(error.into())
+        match error {
+            // Redis is not reachable, the caller should retry later
+            CacheError::RedisPoolError(_)
+            | CacheError::RedisClientError(_)
+            | CacheError::RedisPongError => ApiError::ServiceUnavailable(error.into()),
+            // the connection to Redis was lost while executing the command
+            CacheError::RedisCMDError(ref e) if e.is_connection_dropped() || e.is_io_error() => {
+                ApiError::ServiceUnavailable(error.into())
+            }
+            _ => ApiError::InternalServerError(error.into()),
+        }
+    }
+}
+
+/// Convert AbotError to ApiErrors
+impl From<AbotError> for ApiError {
+    fn from(error: AbotError) -> Self {
+        match error {
+            AbotError::CacheError(error) => error.into(),
+            _ => ApiError::InternalServerError(error.into()),
+        }
     }
 }
 
@@ -195,3 +238,64 @@ impl From<MatrixError> for ApiError {
         ApiError::InternalServerError(error.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    async fn assert_response(error: ApiError, status: StatusCode, body: &str) {
+        let response = error.error_response();
+        assert_eq!(response.status(), status);
+        let bytes = to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(bytes, body);
+    }
+
+    #[actix_web::test]
+    async fn it_responds_with_bad_request() {
+        assert_response(
+            ApiError::BadRequest("member_id is empty".into()),
+            StatusCode::BAD_REQUEST,
+            r#"{"error":"member_id is empty","code":400}"#,
+        )
+        .await;
+    }
+
+    #[actix_web::test]
+    async fn it_responds_with_service_unavailable_when_redis_is_down() {
+        let error: ApiError = CacheError::RedisPongError.into();
+        assert_response(
+            error,
+            StatusCode::SERVICE_UNAVAILABLE,
+            r#"{"error":"Pong response error","code":503}"#,
+        )
+        .await;
+    }
+
+    #[actix_web::test]
+    async fn it_responds_with_service_unavailable_when_the_redis_connection_drops() {
+        let io_error = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "connection reset");
+        let error: ApiError =
+            CacheError::RedisCMDError(mobc_redis::redis::RedisError::from(io_error)).into();
+        assert!(matches!(error, ApiError::ServiceUnavailable(_)));
+
+        // other command errors are not transient
+        let type_error = mobc_redis::redis::RedisError::from((
+            mobc_redis::redis::ErrorKind::TypeError,
+            "unexpected type",
+        ));
+        let error: ApiError = CacheError::RedisCMDError(type_error).into();
+        assert!(matches!(error, ApiError::InternalServerError(_)));
+    }
+
+    #[actix_web::test]
+    async fn it_responds_with_internal_server_error() {
+        let error: ApiError = CacheError::Other("unexpected".into()).into();
+        assert_response(
+            error,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            r#"{"error":"Other error: unexpected","code":500}"#,
+        )
+        .await;
+    }
+}
@@ -0,0 +1,67 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::time::Duration;
+
+/// Returns the timeout applied to connect and complete a request, disabled when 0
+pub fn timeout(timeout_secs: u64) -> Option<Duration> {
+    if timeout_secs > 0 {
+        Some(Duration::from_secs(timeout_secs))
+    } else {
+        None
+    }
+}
+
+/// Returns an HTTP client that fails requests taking longer than the timeout
+pub fn build(timeout_secs: u64) -> reqwest::Client {
+    let mut builder = reqwest::Client::builder();
+    if let Some(timeout) = timeout(timeout_secs) {
+        builder = builder.connect_timeout(timeout).timeout(timeout);
+    }
+    builder.build().expect("valid http client")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[actix_web::test]
+    async fn it_times_out_requests_with_the_configured_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+            .mount(&server)
+            .await;
+
+        let err = build(1).get(server.uri()).send().await.unwrap_err();
+        assert!(err.is_timeout());
+        // disabled when 0
+        assert!(build(0).get(server.uri()).send().await.is_ok());
+    }
+
+    #[test]
+    fn it_disables_the_timeout_when_0() {
+        assert_eq!(timeout(0), None);
+        assert_eq!(timeout(10), Some(Duration::from_secs(10)));
+    }
+}
@@ -21,22 +21,36 @@
 
 mod abot;
 mod api;
+mod breaker;
 mod cache;
+mod coalescer;
 mod config;
 mod errors;
+mod http_client;
 mod matrix;
+mod metrics;
+mod notifier;
+mod rate_limiter;
 mod report;
+mod tasks;
+mod telegram;
+mod watchdog;
+mod webhook;
 
 use crate::abot::Abot;
 use crate::api::routes::routes;
-use crate::config::CONFIG;
-use log::info;
-use std::env;
+use crate::config::{LogFormat, CONFIG};
+use crate::matrix::Matrix;
+use log::{error, info};
+use std::{env, io, io::Write, time};
 
 // use actix::*;
 use actix_cors::Cors;
 use actix_web::{http, middleware, web, App, HttpServer};
 
+// Time (in seconds) to wait for background tasks to finish on shutdown
+const SHUTDOWN_TIMEOUT_SECS: u64 = 10;
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // load configuration
@@ -47,7 +61,19 @@ async fn main() -> std::io::Result<()> {
     } else {
         env::set_var("RUST_LOG", "abot=info");
     }
-    env_logger::try_init().unwrap_or_default();
+    let mut builder = env_logger::Builder::from_default_env();
+    if config.log_format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "timestamp": chrono::Utc::now().to_rfc3339(),
+                "level": record.level().to_string(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{}", line)
+        });
+    }
+    builder.try_init().unwrap_or_default();
 
     info!(
         "{} v{} * {}",
@@ -56,20 +82,31 @@ async fn main() -> std::io::Result<()> {
         env!("CARGO_PKG_DESCRIPTION")
     );
 
-    // authenticate matrix user, load and process commands from matrix rooms
-    Abot::start();
+    // fail fast on a misconfigured bot instead of erroring later in a spawned task
+    if let Err(e) = config.validate() {
+        panic!("Configuration error: {}", e);
+    }
+
+    // send a single test message to validate the matrix setup and exit
+    if !config.send_test_message.is_empty() {
+        return send_test_message(&config.send_test_message).await;
+    }
 
     // create a new instance to be shared with all webhooks
     let abot = Abot::new().await;
 
+    // fetch members, authenticate matrix user, load and process commands from matrix rooms
+    abot.start();
+    let abot_tasks = abot.tasks().clone();
+
     // start http webhooks server
+    let api_unix_socket = config.api_unix_socket.clone();
     let addr = format!("{}:{}", config.api_host, config.api_port);
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
+        let allowed_origins = config.api_cors_allow_origin.clone();
         let cors = Cors::default()
-            .allowed_origin_fn(|origin, _req_head| {
-                let allowed_origins =
-                    env::var("ABOT_API_CORS_ALLOW_ORIGIN").unwrap_or("*".to_string());
-                let allowed_origins = allowed_origins.split(",").collect::<Vec<_>>();
+            .allowed_origin_fn(move |origin, _req_head| {
+                let allowed_origins = allowed_origins.split(',').collect::<Vec<_>>();
                 allowed_origins
                     .iter()
                     .any(|e| e.as_bytes() == origin.as_bytes())
@@ -83,8 +120,98 @@ async fn main() -> std::io::Result<()> {
             .wrap(middleware::Logger::default())
             .wrap(cors)
             .configure(routes)
-    })
-    .bind(addr)?
-    .run()
-    .await
+    });
+
+    // bind to the unix domain socket if defined, otherwise to the tcp address
+    let server = if !api_unix_socket.is_empty() {
+        remove_stale_socket(&api_unix_socket)?;
+        info!("Listening on unix socket {}", api_unix_socket);
+        server.bind_uds(&api_unix_socket)?
+    } else {
+        server.bind(addr)?
+    };
+    let result = server.run().await;
+
+    // signal background tasks to finish their current iteration before exiting
+    abot_tasks
+        .shutdown(time::Duration::from_secs(SHUTDOWN_TIMEOUT_SECS))
+        .await;
+    result
+}
+
+/// Removes a socket file left behind by a previous run, any other file is left untouched
+fn remove_stale_socket(path: &str) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            info!("Removing stale unix socket {}", path);
+            std::fs::remove_file(path)
+        }
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists and is not a unix socket", path),
+        )),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Authenticates the matrix bot user and sends a private test message to the given user
+async fn send_test_message(user_id: &str) -> io::Result<()> {
+    if CONFIG.matrix_disabled {
+        error!("Test message not sent: matrix is disabled (check ABOT_MATRIX_DISABLED or --disable-matrix)");
+        return Err(io::Error::other("matrix is disabled"));
+    }
+
+    let mut matrix = Matrix::new();
+    let result = match matrix.authenticate().await {
+        Ok(()) => {
+            let message = format!(
+                "🤖 {} v{} test message",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            );
+            matrix
+                .send_private_message(user_id, &message, Some(&message))
+                .await
+        }
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(_) => {
+            info!("Test message sent to {}", user_id);
+            Ok(())
+        }
+        Err(e) => {
+            error!("Test message not sent to {}: {}", user_id, e);
+            Err(io::Error::other(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_removes_only_stale_sockets() {
+        let dir = env::temp_dir().join(format!("abot.uds.test.{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let socket = dir.join("abot.sock");
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+        assert!(remove_stale_socket(socket.to_str().unwrap()).is_ok());
+        assert!(!socket.exists());
+        // missing file is fine
+        assert!(remove_stale_socket(socket.to_str().unwrap()).is_ok());
+
+        let file = dir.join("abot.txt");
+        std::fs::write(&file, "keep").unwrap();
+        assert!(remove_stale_socket(file.to_str().unwrap()).is_err());
+        assert!(file.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
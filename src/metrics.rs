@@ -0,0 +1,159 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use prometheus::{Encoder, IntCounter, IntCounterVec, Opts, Registry, TextEncoder};
+
+// SkipReason represents why an alert has not been delivered to a subscriber
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkipReason {
+    Mute,
+    Maintenance,
+    Paused,
+    Pending,
+    Threshold,
+    Snoozed,
+    Quiet,
+    NotWhitelisted,
+    Chain,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mute => write!(f, "mute"),
+            Self::Maintenance => write!(f, "maintenance"),
+            Self::Paused => write!(f, "paused"),
+            Self::Pending => write!(f, "pending"),
+            Self::Threshold => write!(f, "threshold"),
+            Self::Snoozed => write!(f, "snoozed"),
+            Self::Quiet => write!(f, "quiet"),
+            Self::NotWhitelisted => write!(f, "not_whitelisted"),
+            Self::Chain => write!(f, "chain"),
+        }
+    }
+}
+
+/// In-process Prometheus counters shared by all webhooks
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    alerts_received: IntCounter,
+    alerts_delivered: IntCounter,
+    alerts_skipped: IntCounterVec,
+    alerts_failed: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let registry = Registry::new_custom(Some(env!("CARGO_PKG_NAME").into()), None)
+            .expect("valid registry prefix");
+
+        let alerts_received =
+            IntCounter::new("alerts_received_total", "Alerts received from the monitor")
+                .expect("valid metric");
+        let alerts_delivered =
+            IntCounter::new("alerts_delivered_total", "Alerts delivered to subscribers")
+                .expect("valid metric");
+        let alerts_skipped = IntCounterVec::new(
+            Opts::new(
+                "alerts_skipped_total",
+                "Alerts not delivered to subscribers by reason",
+            ),
+            &["reason"],
+        )
+        .expect("valid metric");
+        let alerts_failed = IntCounter::new(
+            "alerts_failed_total",
+            "Alerts that failed to be delivered to subscribers",
+        )
+        .expect("valid metric");
+
+        for collector in [
+            Box::new(alerts_received.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(alerts_delivered.clone()),
+            Box::new(alerts_skipped.clone()),
+            Box::new(alerts_failed.clone()),
+        ] {
+            registry.register(collector).expect("unique metric");
+        }
+
+        Metrics {
+            registry,
+            alerts_received,
+            alerts_delivered,
+            alerts_skipped,
+            alerts_failed,
+        }
+    }
+
+    pub fn inc_received(&self) {
+        self.alerts_received.inc();
+    }
+
+    pub fn inc_delivered(&self) {
+        self.alerts_delivered.inc();
+    }
+
+    pub fn inc_skipped(&self, reason: SkipReason) {
+        self.alerts_skipped
+            .with_label_values(&[&reason.to_string()])
+            .inc();
+    }
+
+    pub fn inc_failed(&self) {
+        self.alerts_failed.inc();
+    }
+
+    /// Returns all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        let encoder = TextEncoder::new();
+        encoder
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap_or_default();
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_renders_counters() {
+        let metrics = Metrics::new();
+        metrics.inc_received();
+        metrics.inc_delivered();
+        metrics.inc_skipped(SkipReason::Mute);
+        metrics.inc_skipped(SkipReason::Mute);
+        let output = metrics.render();
+        assert!(output.contains("abot_alerts_received_total 1"));
+        assert!(output.contains("abot_alerts_delivered_total 1"));
+        assert!(output.contains("abot_alerts_skipped_total{reason=\"mute\"} 2"));
+        assert!(output.contains("abot_alerts_failed_total 0"));
+    }
+}
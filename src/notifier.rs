@@ -0,0 +1,69 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::errors::AbotError;
+use async_trait::async_trait;
+
+/// A backend able to deliver alerts and notifications to users
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Sends a message to the user private channel and returns the id of the message
+    async fn send_private_message(
+        &self,
+        to_user_id: &str,
+        message: &str,
+        formatted_message: Option<&str>,
+    ) -> Result<Option<String>, AbotError>;
+
+    /// Sends a message to the user private channel as a reply in the thread started by the
+    /// root message and returns the id of the message, backends without threads send a
+    /// regular private message
+    async fn send_private_thread_message(
+        &self,
+        to_user_id: &str,
+        message: &str,
+        formatted_message: Option<&str>,
+        _thread_root: &str,
+    ) -> Result<Option<String>, AbotError> {
+        self.send_private_message(to_user_id, message, formatted_message)
+            .await
+    }
+
+    /// Sends a message to the room the user routed the alerts of a member to and returns the
+    /// id of the message, backends without dedicated rooms send a regular private message
+    async fn send_routed_message(
+        &self,
+        to_user_id: &str,
+        _room_id: &str,
+        message: &str,
+        formatted_message: Option<&str>,
+    ) -> Result<Option<String>, AbotError> {
+        self.send_private_message(to_user_id, message, formatted_message)
+            .await
+    }
+
+    /// Sends a message to the public channel and returns the id of the message
+    async fn send_public_message(
+        &self,
+        message: &str,
+        formatted_message: Option<&str>,
+    ) -> Result<Option<String>, AbotError>;
+}
@@ -0,0 +1,144 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::matrix::UserID;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Number of users tracked above which users with a negligible score are forgotten
+const MAX_TRACKED_USERS: usize = 1024;
+// Score below which a user is considered idle
+const IDLE_SCORE: f64 = 0.01;
+
+#[derive(Debug, PartialEq)]
+pub enum Throttle {
+    Allowed,
+    // notify is only true for the first command throttled since the last one allowed
+    Throttled { notify: bool },
+}
+
+#[derive(Debug)]
+struct UserScore {
+    score: f64,
+    updated_at: Instant,
+    notified: bool,
+}
+
+impl UserScore {
+    /// Returns the score at now, halved every half_life
+    fn decayed(&self, now: Instant, half_life: Duration) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.score * 0.5f64.powf(elapsed / half_life.as_secs_f64())
+    }
+}
+
+/// Limits the rate of commands processed by user. Every command adds one to the user score,
+/// which decays exponentially, and commands are throttled while the score would exceed the
+/// burst. The state is shared between clones.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    burst: u32,
+    half_life: Duration,
+    scores: Arc<Mutex<HashMap<UserID, UserScore>>>,
+}
+
+impl RateLimiter {
+    /// Returns a rate limiter that allows bursts of up to burst commands, disabled when burst is 0
+    pub fn new(burst: u32, half_life: Duration) -> RateLimiter {
+        RateLimiter {
+            burst,
+            half_life,
+            scores: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Records a command from the user and returns if it should be processed
+    pub fn check(&self, who: &str, now: Instant) -> Throttle {
+        if self.burst == 0 || self.half_life.is_zero() {
+            return Throttle::Allowed;
+        }
+        let mut scores = self.scores.lock().expect("rate limiter lock");
+        if scores.len() >= MAX_TRACKED_USERS {
+            let half_life = self.half_life;
+            scores.retain(|_, user| user.decayed(now, half_life) >= IDLE_SCORE);
+        }
+        let user = scores.entry(who.to_string()).or_insert(UserScore {
+            score: 0.0,
+            updated_at: now,
+            notified: false,
+        });
+        let score = user.decayed(now, self.half_life);
+        user.updated_at = now;
+        if score + 1.0 > self.burst as f64 {
+            // throttled commands do not add to the score, so that the user recovers
+            user.score = score;
+            let notify = !user.notified;
+            user.notified = true;
+            Throttle::Throttled { notify }
+        } else {
+            user.score = score + 1.0;
+            user.notified = false;
+            Throttle::Allowed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_throttles_bursts_beyond_the_limit() {
+        let limiter = RateLimiter::new(3, Duration::from_secs(60));
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(limiter.check("@alice:matrix.org", now), Throttle::Allowed);
+        }
+        // only the first throttled command is notified
+        assert_eq!(
+            limiter.check("@alice:matrix.org", now),
+            Throttle::Throttled { notify: true }
+        );
+        assert_eq!(
+            limiter.check("@alice:matrix.org", now),
+            Throttle::Throttled { notify: false }
+        );
+        // other users are not affected
+        assert_eq!(limiter.check("@bob:matrix.org", now), Throttle::Allowed);
+        // after one half-life the score drops from 3 to 1.5, leaving room for one more command
+        let later = now + Duration::from_secs(60);
+        assert_eq!(limiter.check("@alice:matrix.org", later), Throttle::Allowed);
+        assert_eq!(
+            limiter.check("@alice:matrix.org", later),
+            Throttle::Throttled { notify: true }
+        );
+    }
+
+    #[test]
+    fn it_never_throttles_when_disabled() {
+        let limiter = RateLimiter::new(0, Duration::from_secs(60));
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(limiter.check("@alice:matrix.org", now), Throttle::Allowed);
+        }
+    }
+}
@@ -19,35 +19,81 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use crate::abot::{HealthCheckId, MemberId, ServiceId, Severity};
-use crate::config::CONFIG;
+use crate::abot::{
+    normalize_member_id, HealthCheckId, MaintenanceMode, MemberId, RecentAlert, ServiceId, Severity,
+};
+use crate::coalescer::CoalescedAlert;
+use crate::config::{Config, CONFIG};
+use crate::matrix::UserID;
+use chrono::{TimeZone, Utc};
 use log::info;
+use std::collections::BTreeMap;
 
 type Body = Vec<String>;
 
+// Short titles for the alert codes raised by ibp-monitor
+const ALERT_CODE_TITLES: [(u32, &str); 4] = [
+    (100, "RPC service offline"),
+    (101, "Chain halted"),
+    (102, "Chain out of sync"),
+    (103, "Low RPC performance"),
+];
+
+// Maximum number of members listed in a single message
+const MEMBERS_PER_MESSAGE: usize = 50;
+
 pub struct Report {
     body: Body,
+    formatted_body: Body,
 }
 
 impl Report {
     pub fn new() -> Report {
-        Report { body: Vec::new() }
+        Report {
+            body: Vec::new(),
+            formatted_body: Vec::new(),
+        }
     }
 
     pub fn add_raw_text(&mut self, t: String) {
+        self.body.push(t.clone());
+        self.formatted_body.push(t);
+    }
+
+    /// Adds a line with a different content for the plain and the formatted (HTML) message
+    pub fn add_text(&mut self, t: String, formatted: String) {
         self.body.push(t);
+        self.formatted_body.push(formatted);
     }
 
     pub fn add_break(&mut self) {
         self.add_raw_text("".into());
     }
 
+    /// Truncates the line at the given index so that both the plain and the formatted message
+    /// fit in max_size bytes, the respective suffix is appended to the truncated line
+    fn fit_line(&mut self, index: usize, max_size: usize, suffix: &str, formatted_suffix: &str) {
+        let overflow = self.message().len().saturating_sub(max_size);
+        if overflow > 0 {
+            let line = &self.body[index];
+            let size = line.len().saturating_sub(overflow + suffix.len());
+            self.body[index] = format!("{}{}", truncate_text(line, size), suffix);
+        }
+        let overflow = self.formatted_message().len().saturating_sub(max_size);
+        if overflow > 0 {
+            let line = &self.formatted_body[index];
+            let size = line.len().saturating_sub(overflow + formatted_suffix.len());
+            self.formatted_body[index] =
+                format!("{}{}", truncate_html(line, size), formatted_suffix);
+        }
+    }
+
     pub fn message(&self) -> String {
         self.body.join("\n")
     }
 
     pub fn formatted_message(&self) -> String {
-        self.body.join("<br>")
+        self.formatted_body.join("<br>")
     }
 
     pub fn log(&self) {
@@ -59,6 +105,33 @@ impl Report {
     }
 }
 
+/// Kind of notice sent to subscribers, which defines the prefix of its title
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportKind {
+    Alert,
+    Maintenance,
+    Recovery,
+}
+
+impl ReportKind {
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Self::Alert => "🚨",
+            Self::Maintenance => "🚧",
+            Self::Recovery => "💚",
+        }
+    }
+}
+
+impl From<&MaintenanceMode> for ReportKind {
+    fn from(mode: &MaintenanceMode) -> Self {
+        match mode {
+            MaintenanceMode::On | MaintenanceMode::Queue => Self::Maintenance,
+            MaintenanceMode::Off => Self::Recovery,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RawAlert {
     pub code: u32,
@@ -68,31 +141,92 @@ pub struct RawAlert {
     pub service_id: ServiceId,
     pub health_check_id: HealthCheckId,
     pub data: String,
+    // the severity sent by the monitor, if the alert has been escalated
+    pub escalated_from: Option<Severity>,
+}
+
+impl RawAlert {
+    /// Returns a sample alert with placeholder data at the given severity
+    pub fn preview(severity: Severity) -> RawAlert {
+        RawAlert {
+            code: 100,
+            message: format!("This is a preview of a {} severity alert", severity),
+            severity,
+            member_id: "ibp-member".into(),
+            service_id: "polkadot-rpc".into(),
+            health_check_id: 0,
+            data: String::new(),
+            escalated_from: None,
+        }
+    }
 }
 
 impl From<RawAlert> for Report {
     /// Converts an ibp-monitor `Alert` into a [`Report`].
     fn from(data: RawAlert) -> Report {
-        let config = CONFIG.clone();
+        Report::from_alert(data, &CONFIG)
+    }
+}
+
+impl Report {
+    /// Converts an ibp-monitor `Alert` into a [`Report`] using the given configuration.
+    pub fn from_alert(data: RawAlert, config: &Config) -> Report {
         let mut report = Report::new();
 
-        report.add_raw_text(format!(
-            "🚨 <b>Alert [{}] ― {}</b> {}",
-            data.code,
-            data.service_id,
-            severity_emoji(data.severity)
-        ));
+        let emoji = severity_emoji(data.severity, config);
+        let prefix = ReportKind::Alert.prefix();
+        report.add_text(
+            format!(
+                "{} <b>Alert [{}] ― {}</b> {}",
+                prefix,
+                alert_code_label(data.code),
+                data.service_id,
+                emoji
+            ),
+            format!(
+                "{} <b>Alert [{}] ― {}</b> {}",
+                prefix,
+                alert_code_label(data.code),
+                escape_html(&data.service_id),
+                emoji
+            ),
+        );
 
         report.add_break();
 
-        report.add_raw_text(format!("💬 {}", data.message,));
+        if let Some(severity) = &data.escalated_from {
+            report.add_raw_text(format!(
+                "⏫ Escalated from {} severity after repeated occurrences",
+                severity
+            ));
+        }
+
+        let message_index = report.body.len();
+        report.add_text(
+            format!("💬 {}", data.message),
+            format!("💬 {}", escape_html(&data.message)),
+        );
 
+        let health_check_url = format!(
+            "{}/healthCheck/{}",
+            config.ibp_monitor_url, data.health_check_id
+        );
         report.add_raw_text(format!(
-            "🩺 Health Check <a href=\"{}/healthCheck/{}\">#{}</a>",
-            config.ibp_monitor_url, data.health_check_id, data.health_check_id,
+            "🩺 Health Check <a href=\"{}\">#{}</a>",
+            health_check_url, data.health_check_id,
         ));
 
-        report.add_raw_text(format!("🦸 Member {}", data.member_id));
+        if let Some(response_time) = response_time_ms(&data.data) {
+            report.add_raw_text(response_time_line(
+                response_time,
+                config.alert_latency_threshold_ms,
+            ));
+        }
+
+        report.add_text(
+            format!("🦸 Member {}", data.member_id),
+            format!("🦸 Member {}", member_html(&data.member_id, config)),
+        );
 
         // let mut clode_block = String::from("<pre><code>");
         // clode_block.push_str(&format!("{}", data.data.to_string()));
@@ -102,6 +236,14 @@ impl From<RawAlert> for Report {
         report.add_raw_text("——".into());
         report.add_break();
 
+        // long monitor messages are truncated so that the homeserver does not reject the report
+        report.fit_line(
+            message_index,
+            config.report_max_size,
+            &format!("… {}", health_check_url),
+            &format!("… <a href=\"{}\">more</a>", health_check_url),
+        );
+
         // Log report
         report.log();
 
@@ -109,10 +251,689 @@ impl From<RawAlert> for Report {
     }
 }
 
-fn severity_emoji(severity: Severity) -> String {
+/// Returns the slowest response time (in milliseconds) reported by the health checks
+/// serialized in the alert data, if any
+fn response_time_ms(data: &str) -> Option<u64> {
+    let health_checks: Vec<serde_json::Value> = serde_json::from_str(data).ok()?;
+    health_checks
+        .iter()
+        .filter_map(|health_check| health_check.get("responseTimeMs")?.as_f64())
+        .filter(|response_time| response_time.is_finite() && *response_time >= 0.0)
+        .map(|response_time| response_time.round() as u64)
+        .max()
+}
+
+/// Formats the response time, flagging it when above the latency threshold (disabled when 0)
+fn response_time_line(response_time: u64, threshold: u64) -> String {
+    if threshold > 0 && response_time > threshold {
+        format!(
+            "‣ ⏱ {} ms 🐢 above the {} ms latency threshold",
+            response_time, threshold
+        )
+    } else {
+        format!("‣ ⏱ {} ms", response_time)
+    }
+}
+
+/// Returns the link configured for the member, matrix rooms are linked with a matrix.to permalink
+fn member_link(member_id: &str, config: &Config) -> Option<String> {
+    config.member_links.iter().find_map(|entry| {
+        let (member, link) = entry.split_once('=')?;
+        if normalize_member_id(member) != member_id || link.trim().is_empty() {
+            return None;
+        }
+        let link = link.trim();
+        if link.starts_with('#') || link.starts_with('!') {
+            Some(format!("https://matrix.to/#/{}", link))
+        } else {
+            Some(link.to_string())
+        }
+    })
+}
+
+/// Renders the member name as a link (a pill for matrix rooms) if one is configured
+fn member_html(member_id: &str, config: &Config) -> String {
+    match member_link(member_id, config) {
+        Some(link) => format!(
+            "<a href=\"{}\">{}</a>",
+            escape_html(&link),
+            escape_html(member_id)
+        ),
+        None => escape_html(member_id),
+    }
+}
+
+fn severity_emoji(severity: Severity, config: &Config) -> String {
     match severity {
-        Severity::High => String::from("🔥🔥🔥"),
-        Severity::Medium => String::from("🔥🔥"),
-        Severity::Low => String::from("🔥"),
+        Severity::High => config.severity_emoji_high.to_string(),
+        Severity::Medium => config.severity_emoji_medium.to_string(),
+        Severity::Low => config.severity_emoji_low.to_string(),
+    }
+}
+
+/// Formats the most recent alerts from a member into a summary message
+pub fn history_message(member_id: &str, recent_alerts: &[RecentAlert]) -> String {
+    if recent_alerts.is_empty() {
+        return format!("📜 No recent alerts from <b>{}</b>", escape_html(member_id));
+    }
+    let mut message = format!(
+        "📜 Last {} alerts from <b>{}</b>:<br>",
+        recent_alerts.len(),
+        escape_html(member_id)
+    );
+    for alert in recent_alerts {
+        message.push_str(&recent_alert_line(alert));
+    }
+    message
+}
+
+/// Formats the alerts queued during maintenance into a digest for each subscriber,
+/// including only the severities the subscriber is subscribed to
+pub fn maintenance_digests(
+    member_id: &str,
+    queued_alerts: &[RecentAlert],
+    subscriptions: &BTreeMap<UserID, Vec<Severity>>,
+) -> Vec<(UserID, String)> {
+    subscriptions
+        .iter()
+        .filter_map(|(subscriber, severities)| {
+            let alerts: Vec<&RecentAlert> = queued_alerts
+                .iter()
+                .filter(|alert| severities.contains(&alert.severity))
+                .collect();
+            if alerts.is_empty() {
+                return None;
+            }
+            let mut message = format!(
+                "📥 {} alerts from <b>{}</b> during maintenance:<br>",
+                alerts.len(),
+                escape_html(member_id)
+            );
+            for alert in alerts {
+                message.push_str(&recent_alert_line(alert));
+            }
+            Some((subscriber.to_string(), message))
+        })
+        .collect()
+}
+
+fn recent_alert_line(alert: &RecentAlert) -> String {
+    let datetime = match Utc.timestamp_opt(alert.timestamp, 0).single() {
+        Some(datetime) => datetime.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        None => alert.timestamp.to_string(),
+    };
+    format!(
+        "<code>{}</code> ― [{}] {} {}<br>",
+        datetime,
+        alert_code_label(alert.code),
+        escape_html(&alert.service_id),
+        alert.severity
+    )
+}
+
+/// Escapes untrusted text so that it is rendered as is in HTML formatted messages
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Truncates the text to at most max_size bytes without splitting a UTF-8 character
+fn truncate_text(text: &str, max_size: usize) -> &str {
+    if text.len() <= max_size {
+        return text;
+    }
+    let mut end = max_size;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Truncates escaped HTML text to at most max_size bytes without splitting a character entity
+fn truncate_html(text: &str, max_size: usize) -> &str {
+    let truncated = truncate_text(text, max_size);
+    match truncated.rfind('&') {
+        Some(i) if truncated.len() < text.len() && !truncated[i..].contains(';') => &truncated[..i],
+        _ => truncated,
+    }
+}
+
+/// Formats the alerts buffered during the coalescing window into a single digest report
+pub fn digest_report(member_id: &str, alerts: &[CoalescedAlert]) -> Report {
+    let mut report = Report::new();
+    report.add_text(
+        format!(
+            "{} {} alerts from {}:",
+            ReportKind::Alert.prefix(),
+            alerts.len(),
+            member_id
+        ),
+        format!(
+            "{} <b>{} alerts from {}</b>:",
+            ReportKind::Alert.prefix(),
+            alerts.len(),
+            escape_html(member_id)
+        ),
+    );
+    for alert in alerts {
+        report.add_text(
+            format!(
+                "[{}] ― {} {}",
+                alert_code_label(alert.code),
+                alert.service_id,
+                alert.severity
+            ),
+            format!(
+                "[{}] ― {} {}",
+                alert_code_label(alert.code),
+                escape_html(&alert.service_id),
+                alert.severity
+            ),
+        );
+    }
+    report
+}
+
+/// Alert counters of a member for a day, as cached in the stats hashes
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DailyStats {
+    pub by_code: BTreeMap<String, u64>,
+    pub by_severity: BTreeMap<String, u64>,
+    pub by_service: BTreeMap<String, u64>,
+}
+
+/// Formats the daily digest with the alert counters of each member subscribed
+pub fn daily_digest_message(date: &str, stats: &[(MemberId, &DailyStats)]) -> String {
+    let mut message = format!("📊 Daily digest ― {}:<br>", date);
+    for (member_id, stats) in stats {
+        let total: u64 = stats.by_severity.values().sum();
+        if total == 0 {
+            message.push_str(&format!(
+                "<b>{}</b> ― no alerts 💚<br>",
+                escape_html(member_id)
+            ));
+            continue;
+        }
+        let severities: Vec<String> = [Severity::High, Severity::Medium, Severity::Low]
+            .iter()
+            .filter_map(|severity| {
+                let count = stats.by_severity.get(&severity.to_string())?;
+                Some(format!("{} {}", severity, count))
+            })
+            .collect();
+        let codes: Vec<String> = by_count(&stats.by_code)
+            .iter()
+            .map(|(code, count)| {
+                let label = code
+                    .parse::<u32>()
+                    .map(alert_code_label)
+                    .unwrap_or_else(|_| code.to_string());
+                format!("[{}] {}", escape_html(&label), count)
+            })
+            .collect();
+        let services: Vec<String> = by_count(&stats.by_service)
+            .iter()
+            .map(|(service_id, count)| format!("{} {}", escape_html(service_id), count))
+            .collect();
+        message.push_str(&format!(
+            "<b>{}</b> ― {} alerts · {} · {} · {}<br>",
+            escape_html(member_id),
+            total,
+            severities.join(", "),
+            codes.join(", "),
+            services.join(", ")
+        ));
+    }
+    message
+}
+
+/// Sorts the counters by the highest count first, then by name
+fn by_count(counters: &BTreeMap<String, u64>) -> Vec<(&String, &u64)> {
+    let mut counters: Vec<(&String, &u64)> = counters.iter().collect();
+    counters.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    counters
+}
+
+/// Formats the announcement of the members newly added to the members.json file
+pub fn new_members_message(member_ids: &[MemberId]) -> String {
+    let members: Vec<String> = member_ids
+        .iter()
+        .map(|member_id| format!("<code>{}</code>", escape_html(member_id)))
+        .collect();
+    format!(
+        "🆕 New members available to subscribe: {}",
+        members.join(", ")
+    )
+}
+
+/// Formats the list of members into one or more messages with up to MEMBERS_PER_MESSAGE members each
+pub fn members_messages(member_ids: &[MemberId]) -> Vec<String> {
+    if member_ids.is_empty() {
+        return vec![
+            "🦸 No members loaded yet, members are fetched from the members.json file".into(),
+        ];
+    }
+    member_ids
+        .chunks(MEMBERS_PER_MESSAGE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut message = if i == 0 {
+                format!("🦸 {} members:<br>", member_ids.len())
+            } else {
+                String::new()
+            };
+            for member_id in chunk {
+                message.push_str(&format!("<code>{}</code><br>", escape_html(member_id)));
+            }
+            message
+        })
+        .collect()
+}
+
+/// Formats the recent alerts of each member as CSV, one alert per line
+pub fn alerts_csv(records: &[(MemberId, RecentAlert)]) -> String {
+    let mut csv = String::from("datetime,member_id,code,title,severity,service_id\n");
+    for (member_id, alert) in records {
+        let datetime = match Utc.timestamp_opt(alert.timestamp, 0).single() {
+            Some(datetime) => datetime.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            None => alert.timestamp.to_string(),
+        };
+        let title = ALERT_CODE_TITLES
+            .iter()
+            .find(|(code, _)| *code == alert.code)
+            .map(|(_, title)| *title)
+            .unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            datetime,
+            csv_field(member_id),
+            alert.code,
+            csv_field(title),
+            alert.severity,
+            csv_field(&alert.service_id)
+        ));
+    }
+    csv
+}
+
+/// Quotes a CSV field if it contains separators, quotes or line breaks
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Returns the alert code followed by its title, or just the code if it is unknown
+fn alert_code_label(code: u32) -> String {
+    match ALERT_CODE_TITLES.iter().find(|(c, _)| *c == code) {
+        Some((_, title)) => format!("{} — {}", code, title),
+        None => code.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_alert(code: u32) -> RawAlert {
+        RawAlert {
+            code,
+            severity: Severity::High,
+            message: "RPC service is offline".into(),
+            member_id: "turboflakes".into(),
+            service_id: "polkadot-rpc".into(),
+            health_check_id: 1,
+            data: String::new(),
+            escalated_from: None,
+        }
+    }
+
+    #[test]
+    fn it_renders_known_alert_code_with_title() {
+        assert_eq!(alert_code_label(100), "100 — RPC service offline");
+        let report = Report::from(raw_alert(100));
+        assert!(report
+            .formatted_message()
+            .contains("Alert [100 — RPC service offline] ― polkadot-rpc"));
+    }
+
+    #[test]
+    fn it_renders_a_preview_with_the_severity_emoji() {
+        for (severity, emoji) in [
+            (Severity::High, &CONFIG.severity_emoji_high),
+            (Severity::Medium, &CONFIG.severity_emoji_medium),
+            (Severity::Low, &CONFIG.severity_emoji_low),
+        ] {
+            let report = Report::from(RawAlert::preview(severity.clone()));
+            assert!(report.message().contains(emoji.as_str()), "{}", severity);
+            assert!(report
+                .message()
+                .contains(&format!("preview of a {} severity alert", severity)));
+        }
+    }
+
+    #[test]
+    fn it_prefixes_titles_by_report_kind() {
+        assert!(Report::from(raw_alert(100)).message().starts_with("🚨 "));
+        assert_eq!(
+            ReportKind::from(&MaintenanceMode::On),
+            ReportKind::Maintenance
+        );
+        assert_eq!(
+            ReportKind::from(&MaintenanceMode::Queue),
+            ReportKind::Maintenance
+        );
+        assert_eq!(
+            ReportKind::from(&MaintenanceMode::Off),
+            ReportKind::Recovery
+        );
+        assert_eq!(ReportKind::Maintenance.prefix(), "🚧");
+        assert_eq!(ReportKind::Recovery.prefix(), "💚");
+    }
+
+    #[test]
+    fn it_renders_unknown_alert_code_as_number() {
+        assert_eq!(alert_code_label(999), "999");
+        let report = Report::from(raw_alert(999));
+        assert!(report
+            .formatted_message()
+            .contains("Alert [999] ― polkadot-rpc"));
+    }
+
+    #[test]
+    fn it_formats_the_daily_digest_from_stats() {
+        let counters = |entries: &[(&str, u64)]| -> BTreeMap<String, u64> {
+            entries
+                .iter()
+                .map(|(name, count)| (name.to_string(), *count))
+                .collect()
+        };
+        let stats = DailyStats {
+            by_code: counters(&[("100", 1), ("103", 4)]),
+            by_severity: counters(&[("high", 1), ("low", 4)]),
+            by_service: counters(&[("kusama-rpc", 2), ("polkadot-rpc", 3)]),
+        };
+        let empty = DailyStats::default();
+        assert_eq!(
+            daily_digest_message(
+                "2023-06-01",
+                &[
+                    ("turboflakes".to_string(), &stats),
+                    ("stakeplus".to_string(), &empty)
+                ]
+            ),
+            "📊 Daily digest ― 2023-06-01:<br>\
+            <b>turboflakes</b> ― 5 alerts · high 1, low 4 · [103 — Low RPC performance] 4, [100 — RPC service offline] 1 · polkadot-rpc 3, kusama-rpc 2<br>\
+            <b>stakeplus</b> ― no alerts 💚<br>"
+        );
+    }
+
+    #[test]
+    fn it_formats_history_message() {
+        let recent_alerts = vec![RecentAlert {
+            timestamp: 1685620800,
+            code: 100,
+            severity: Severity::High,
+            service_id: "polkadot-rpc".into(),
+        }];
+        assert_eq!(
+            history_message("turboflakes", &recent_alerts),
+            "📜 Last 1 alerts from <b>turboflakes</b>:<br><code>2023-06-01 12:00:00 UTC</code> ― [100 — RPC service offline] polkadot-rpc high<br>"
+        );
+        assert_eq!(
+            history_message("turboflakes", &[]),
+            "📜 No recent alerts from <b>turboflakes</b>"
+        );
+    }
+
+    #[test]
+    fn it_formats_maintenance_digests_by_subscribed_severity() {
+        let queued_alerts = vec![
+            RecentAlert {
+                timestamp: 1685620800,
+                code: 100,
+                severity: Severity::High,
+                service_id: "polkadot-rpc".into(),
+            },
+            RecentAlert {
+                timestamp: 1685620860,
+                code: 103,
+                severity: Severity::Low,
+                service_id: "kusama-rpc".into(),
+            },
+        ];
+        let mut subscriptions: BTreeMap<UserID, Vec<Severity>> = BTreeMap::new();
+        subscriptions.insert("@alice:matrix.org".into(), vec![Severity::High]);
+        subscriptions.insert("@bob:matrix.org".into(), vec![Severity::Medium]);
+        subscriptions.insert(
+            "@carol:matrix.org".into(),
+            vec![Severity::High, Severity::Low],
+        );
+
+        let digests = maintenance_digests("turboflakes", &queued_alerts, &subscriptions);
+        assert_eq!(digests.len(), 2);
+        assert_eq!(digests[0].0, "@alice:matrix.org");
+        assert_eq!(
+            digests[0].1,
+            "📥 1 alerts from <b>turboflakes</b> during maintenance:<br><code>2023-06-01 12:00:00 UTC</code> ― [100 — RPC service offline] polkadot-rpc high<br>"
+        );
+        assert_eq!(digests[1].0, "@carol:matrix.org");
+        assert!(digests[1].1.starts_with("📥 2 alerts"));
+        assert!(maintenance_digests("turboflakes", &[], &subscriptions).is_empty());
+    }
+
+    #[test]
+    fn it_renders_custom_severity_emoji() {
+        let config: Config = envy::prefixed("ABOT_")
+            .from_iter(vec![
+                ("ABOT_API_KEYS".to_string(), "test".to_string()),
+                ("ABOT_SEVERITY_EMOJI_HIGH".to_string(), "🚒".to_string()),
+            ])
+            .unwrap();
+        let report = Report::from_alert(raw_alert(100), &config);
+        assert!(report.formatted_message().contains("polkadot-rpc</b> 🚒"));
+        assert_eq!(severity_emoji(Severity::Low, &config), "🔥");
+    }
+
+    #[test]
+    fn it_renders_the_slowest_health_check_response_time() {
+        let mut alert = raw_alert(103);
+        alert.data =
+            r#"[{"responseTimeMs":350.4},{"responseTimeMs":1200},{"status":"error"}]"#.into();
+        let report = Report::from(alert);
+        assert!(report.message().contains("‣ ⏱ 1200 ms"));
+        assert!(!report.message().contains("🐢"));
+        // alerts without health check response times are rendered as before
+        assert!(!Report::from(raw_alert(103)).message().contains("⏱"));
+        assert_eq!(response_time_ms("not json"), None);
+    }
+
+    #[test]
+    fn it_flags_response_times_above_the_latency_threshold() {
+        assert_eq!(
+            response_time_line(1200, 1000),
+            "‣ ⏱ 1200 ms 🐢 above the 1000 ms latency threshold"
+        );
+        assert_eq!(response_time_line(1000, 1000), "‣ ⏱ 1000 ms");
+        // disabled when 0
+        assert_eq!(response_time_line(1200, 0), "‣ ⏱ 1200 ms");
+    }
+
+    #[test]
+    fn it_annotates_escalated_alerts() {
+        let report = Report::from(raw_alert(100));
+        assert!(!report.message().contains("Escalated"));
+        let mut alert = raw_alert(100);
+        alert.escalated_from = Some(Severity::Low);
+        let report = Report::from(alert);
+        assert!(report
+            .message()
+            .contains("⏫ Escalated from low severity after repeated occurrences"));
+    }
+
+    #[test]
+    fn it_links_members_with_a_configured_url() {
+        let config: Config = envy::prefixed("ABOT_")
+            .from_iter(vec![
+                ("ABOT_API_KEYS".to_string(), "test".to_string()),
+                (
+                    "ABOT_MEMBER_LINKS".to_string(),
+                    "TurboFlakes=https://turboflakes.io,stake.plus=#stakeplus:matrix.org"
+                        .to_string(),
+                ),
+            ])
+            .unwrap();
+        let report = Report::from_alert(raw_alert(100), &config);
+        assert!(report
+            .formatted_message()
+            .contains("🦸 Member <a href=\"https://turboflakes.io\">turboflakes</a>"));
+        assert!(report.message().contains("🦸 Member turboflakes"));
+        assert_eq!(
+            member_html("stake.plus", &config),
+            "<a href=\"https://matrix.to/#/#stakeplus:matrix.org\">stake.plus</a>"
+        );
+
+        let mut alert = raw_alert(100);
+        alert.member_id = "dwellir".into();
+        let report = Report::from_alert(alert, &config);
+        assert!(report.formatted_message().contains("🦸 Member dwellir"));
+    }
+
+    #[test]
+    fn it_escapes_untrusted_text_in_formatted_message() {
+        let mut alert = raw_alert(100);
+        alert.member_id = "<b>turbo</b>".into();
+        alert.service_id = "rpc & co".into();
+        alert.message = "latency > 5s <script>".into();
+        let report = Report::from(alert);
+        let formatted = report.formatted_message();
+        assert!(formatted.contains("🦸 Member &lt;b&gt;turbo&lt;/b&gt;"));
+        assert!(formatted.contains("― rpc &amp; co</b>"));
+        assert!(formatted.contains("💬 latency &gt; 5s &lt;script&gt;"));
+        assert!(!formatted.contains("<script>"));
+        // plain message is left untouched
+        let message = report.message();
+        assert!(message.contains("🦸 Member <b>turbo</b>"));
+        assert!(message.contains("💬 latency > 5s <script>"));
+    }
+
+    #[test]
+    fn it_escapes_html() {
+        assert_eq!(escape_html("turboflakes"), "turboflakes");
+        assert_eq!(
+            escape_html("<a href=\"x\">'&'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn it_truncates_text_on_char_boundaries() {
+        assert_eq!(truncate_text("turboflakes", 20), "turboflakes");
+        assert_eq!(truncate_text("turboflakes", 5), "turbo");
+        // 🔥 takes 4 bytes
+        assert_eq!(truncate_text("a🔥b", 3), "a");
+        assert_eq!(truncate_html("a &amp; b", 4), "a ");
+        assert_eq!(truncate_html("a &amp; b", 7), "a &amp;");
+        assert_eq!(truncate_html("a &amp;", 7), "a &amp;");
+    }
+
+    #[test]
+    fn it_truncates_an_overlong_message() {
+        let config: Config = envy::prefixed("ABOT_")
+            .from_iter(vec![
+                ("ABOT_API_KEYS".to_string(), "test".to_string()),
+                ("ABOT_REPORT_MAX_SIZE".to_string(), "512".to_string()),
+            ])
+            .unwrap();
+        let mut alert = raw_alert(100);
+        alert.message = "🔥 & <timeout> ".repeat(100);
+        let report = Report::from_alert(alert, &config);
+
+        let formatted = report.formatted_message();
+        assert!(formatted.len() <= 512);
+        assert!(formatted.contains("🦸 Member turboflakes"));
+        assert!(formatted.contains("… <a href=\"/healthCheck/1\">more</a>"));
+        let message = report.message();
+        assert!(message.len() <= 512);
+        assert!(message.contains("… /healthCheck/1"));
+
+        // short messages are left untouched
+        let report = Report::from_alert(raw_alert(100), &config);
+        assert!(report
+            .formatted_message()
+            .contains("💬 RPC service is offline<br>"));
+    }
+
+    #[test]
+    fn it_formats_alerts_as_csv() {
+        let records = vec![
+            (
+                "turboflakes".to_string(),
+                RecentAlert {
+                    timestamp: 1685620800,
+                    code: 100,
+                    severity: Severity::High,
+                    service_id: "polkadot-rpc".into(),
+                },
+            ),
+            (
+                "stake,plus".to_string(),
+                RecentAlert {
+                    timestamp: 1685620860,
+                    code: 999,
+                    severity: Severity::Low,
+                    service_id: "kusama \"rpc\"".into(),
+                },
+            ),
+        ];
+        assert_eq!(
+            alerts_csv(&records),
+            "datetime,member_id,code,title,severity,service_id\n\
+             2023-06-01T12:00:00Z,turboflakes,100,RPC service offline,high,polkadot-rpc\n\
+             2023-06-01T12:01:00Z,\"stake,plus\",999,,low,\"kusama \"\"rpc\"\"\"\n"
+        );
+        assert_eq!(
+            alerts_csv(&[]),
+            "datetime,member_id,code,title,severity,service_id\n"
+        );
+    }
+
+    #[test]
+    fn it_formats_new_members_message() {
+        assert_eq!(
+            new_members_message(&["stakeplus".into(), "a&b".into()]),
+            "🆕 New members available to subscribe: <code>stakeplus</code>, <code>a&amp;b</code>"
+        );
+    }
+
+    #[test]
+    fn it_formats_members_messages() {
+        assert_eq!(
+            members_messages(&[]),
+            vec!["🦸 No members loaded yet, members are fetched from the members.json file"]
+        );
+        assert_eq!(
+            members_messages(&["amforc".into(), "turboflakes".into()]),
+            vec!["🦸 2 members:<br><code>amforc</code><br><code>turboflakes</code><br>"]
+        );
+        let member_ids: Vec<MemberId> = (0..MEMBERS_PER_MESSAGE + 1)
+            .map(|i| format!("member-{}", i))
+            .collect();
+        let messages = members_messages(&member_ids);
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("🦸 51 members:<br>"));
+        assert_eq!(messages[1], "<code>member-50</code><br>");
     }
 }
@@ -0,0 +1,116 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use async_std::task::JoinHandle;
+use log::{info, warn};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time;
+
+// Interval at which an interruptible sleep checks for the shutdown signal
+const SHUTDOWN_CHECK_INTERVAL_MS: u64 = 100;
+
+/// Registry of the background tasks, used to signal them to stop on shutdown
+/// and to wait for them to finish their current iteration
+#[derive(Clone, Default)]
+pub struct Tasks {
+    shutdown: Arc<AtomicBool>,
+    handles: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl Tasks {
+    /// Spawns and registers a background task
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handle = async_std::task::spawn(future);
+        let mut handles = self.handles.lock().unwrap_or_else(|e| e.into_inner());
+        handles.push(handle);
+    }
+
+    /// Returns true once shutdown has been signaled, tasks should stop looping
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// Sleeps for the given duration or until shutdown is signaled
+    pub async fn sleep(&self, duration: time::Duration) {
+        let step = time::Duration::from_millis(SHUTDOWN_CHECK_INTERVAL_MS);
+        let mut remaining = duration;
+        while !remaining.is_zero() && !self.is_shutdown() {
+            let interval = remaining.min(step);
+            async_std::task::sleep(interval).await;
+            remaining -= interval;
+        }
+    }
+
+    /// Signals all tasks to stop and waits for them up to the timeout,
+    /// returns true if every task finished in time
+    pub async fn shutdown(&self, timeout: time::Duration) -> bool {
+        self.shutdown.store(true, Ordering::SeqCst);
+        let handles: Vec<JoinHandle<()>> = {
+            let mut handles = self.handles.lock().unwrap_or_else(|e| e.into_inner());
+            handles.drain(..).collect()
+        };
+        info!("Waiting for {} background task(s) to finish", handles.len());
+        let joined = async_std::future::timeout(timeout, async {
+            for handle in handles {
+                handle.await;
+            }
+        })
+        .await;
+        if joined.is_err() {
+            warn!("Background tasks did not finish within {:?}", timeout);
+        }
+        joined.is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+
+    #[actix_web::test]
+    async fn it_stops_a_signaled_task() {
+        let tasks = Tasks::default();
+        let iterations = Arc::new(AtomicU32::new(0));
+        let (t, i) = (tasks.clone(), iterations.clone());
+        tasks.spawn(async move {
+            while !t.is_shutdown() {
+                i.fetch_add(1, Ordering::SeqCst);
+                t.sleep(time::Duration::from_secs(60)).await;
+            }
+        });
+        async_std::task::sleep(time::Duration::from_millis(50)).await;
+        assert!(tasks.shutdown(time::Duration::from_secs(5)).await);
+        assert_eq!(iterations.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_web::test]
+    async fn it_times_out_waiting_for_a_stuck_task() {
+        let tasks = Tasks::default();
+        tasks.spawn(async_std::task::sleep(time::Duration::from_secs(60)));
+        assert!(!tasks.shutdown(time::Duration::from_millis(50)).await);
+    }
+}
@@ -0,0 +1,187 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::cache::{create_or_await_pool, get_conn, CacheKey, RedisPool};
+use crate::config::CONFIG;
+use crate::errors::{AbotError, CacheError};
+use crate::http_client;
+use crate::notifier::Notifier;
+use async_trait::async_trait;
+use log::debug;
+use redis::aio::Connection;
+use serde::{Deserialize, Serialize};
+
+const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+
+pub type ChatID = String;
+
+#[derive(Serialize, Debug)]
+struct SendMessageRequest {
+    chat_id: ChatID,
+    text: String,
+    parse_mode: String,
+    disable_web_page_preview: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct TelegramResponse {
+    ok: bool,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    result: Option<TelegramMessage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TelegramMessage {
+    message_id: i64,
+}
+
+#[derive(Clone)]
+pub struct Telegram {
+    client: reqwest::Client,
+    cache: RedisPool,
+}
+
+impl Telegram {
+    pub fn new() -> Telegram {
+        Telegram {
+            client: http_client::build(CONFIG.http_timeout_secs),
+            cache: create_or_await_pool(CONFIG.clone()),
+        }
+    }
+
+    // Sending messages
+    // https://core.telegram.org/bots/api#sendmessage
+    async fn send_message(&self, chat_id: &str, text: &str) -> Result<Option<String>, AbotError> {
+        let config = CONFIG.clone();
+        let req = SendMessageRequest {
+            chat_id: chat_id.to_string(),
+            text: text.to_string(),
+            parse_mode: "HTML".to_string(),
+            disable_web_page_preview: true,
+        };
+        let res = self
+            .client
+            .post(format!(
+                "{}/bot{}/sendMessage",
+                TELEGRAM_API_URL, config.telegram_bot_token
+            ))
+            .json(&req)
+            .send()
+            .await?;
+        let response = res.json::<TelegramResponse>().await?;
+        if response.ok {
+            Ok(response
+                .result
+                .map(|message| message.message_id.to_string()))
+        } else {
+            Err(AbotError::TelegramError(response.description))
+        }
+    }
+}
+
+impl Default for Telegram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Notifier for Telegram {
+    async fn send_private_message(
+        &self,
+        to_user_id: &str,
+        message: &str,
+        formatted_message: Option<&str>,
+    ) -> Result<Option<String>, AbotError> {
+        // only users that linked a telegram chat receive private messages
+        let mut conn = get_conn(&self.cache).await?;
+        let chat_id = redis::cmd("GET")
+            .arg(CacheKey::TelegramChat(to_user_id.to_string()))
+            .query_async::<Connection, Option<ChatID>>(&mut conn)
+            .await
+            .map_err(CacheError::RedisCMDError)?;
+
+        match chat_id {
+            Some(chat_id) => {
+                self.send_message(&chat_id, &telegram_text(message, formatted_message))
+                    .await
+            }
+            None => {
+                debug!("No telegram chat linked for user {}", to_user_id);
+                Ok(None)
+            }
+        }
+    }
+
+    async fn send_public_message(
+        &self,
+        message: &str,
+        formatted_message: Option<&str>,
+    ) -> Result<Option<String>, AbotError> {
+        let config = CONFIG.clone();
+        if config.telegram_chat_id.is_empty() {
+            return Ok(None);
+        }
+        self.send_message(
+            &config.telegram_chat_id,
+            &telegram_text(message, formatted_message),
+        )
+        .await
+    }
+}
+
+/// Telegram HTML does not support line breaks tags, new lines are used instead
+fn telegram_text(message: &str, formatted_message: Option<&str>) -> String {
+    match formatted_message {
+        Some(formatted) => formatted.replace("<br>", "\n"),
+        None => message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_converts_line_breaks_for_telegram() {
+        assert_eq!(
+            telegram_text("a\nb", Some("<b>a</b><br>b")),
+            "<b>a</b>\nb".to_string()
+        );
+        assert_eq!(telegram_text("a\nb", None), "a\nb".to_string());
+    }
+
+    #[test]
+    fn serialize_send_message_request() {
+        let req = SendMessageRequest {
+            chat_id: "-1001234".to_string(),
+            text: "🚨".to_string(),
+            parse_mode: "HTML".to_string(),
+            disable_web_page_preview: true,
+        };
+        assert_eq!(
+            serde_json::to_string(&req).unwrap(),
+            r#"{"chat_id":"-1001234","text":"🚨","parse_mode":"HTML","disable_web_page_preview":true}"#
+        );
+    }
+}
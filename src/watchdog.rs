@@ -0,0 +1,107 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Tracks when the last alert was received from the monitor, so that a monitor that
+/// silently stopped pushing alerts is noticed. The state is shared between clones.
+#[derive(Clone, Debug)]
+pub struct Watchdog {
+    window: Duration,
+    last_seen: Arc<Mutex<Instant>>,
+    stale: Arc<AtomicBool>,
+}
+
+impl Watchdog {
+    /// Returns a watchdog that considers the monitor stale when no alert is received
+    /// within the window, disabled when the window is 0
+    pub fn new(window: Duration, now: Instant) -> Watchdog {
+        Watchdog {
+            window,
+            last_seen: Arc::new(Mutex::new(now)),
+            stale: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.window.is_zero()
+    }
+
+    /// Records an alert received from the monitor
+    pub fn record(&self, now: Instant) {
+        *self.last_seen.lock().expect("watchdog lock") = now;
+    }
+
+    /// Returns true if no alert has been received within the window
+    pub fn is_stale(&self, now: Instant) -> bool {
+        let last_seen = *self.last_seen.lock().expect("watchdog lock");
+        self.is_enabled() && now.saturating_duration_since(last_seen) > self.window
+    }
+
+    /// Returns Some(stale) when the freshness changed since the previous check
+    pub fn check(&self, now: Instant) -> Option<bool> {
+        let stale = self.is_stale(now);
+        if self.stale.swap(stale, Ordering::SeqCst) != stale {
+            Some(stale)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the time since the last alert was received
+    pub fn silence(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(*self.last_seen.lock().expect("watchdog lock"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_triggers_when_no_alert_is_received_within_the_window() {
+        let now = Instant::now();
+        let watchdog = Watchdog::new(Duration::from_secs(60), now);
+        assert!(!watchdog.is_stale(now + Duration::from_secs(60)));
+        assert_eq!(watchdog.check(now + Duration::from_secs(30)), None);
+        // the transition to stale is reported once
+        assert!(watchdog.is_stale(now + Duration::from_secs(61)));
+        assert_eq!(watchdog.check(now + Duration::from_secs(61)), Some(true));
+        assert_eq!(watchdog.check(now + Duration::from_secs(90)), None);
+        // an alert received makes it fresh again
+        watchdog.record(now + Duration::from_secs(100));
+        assert_eq!(watchdog.check(now + Duration::from_secs(100)), Some(false));
+        assert_eq!(
+            watchdog.silence(now + Duration::from_secs(110)),
+            Duration::from_secs(10)
+        );
+    }
+
+    #[test]
+    fn it_is_never_stale_when_disabled() {
+        let now = Instant::now();
+        let watchdog = Watchdog::new(Duration::ZERO, now);
+        assert!(!watchdog.is_stale(now + Duration::from_secs(86400)));
+        assert_eq!(watchdog.check(now + Duration::from_secs(86400)), None);
+    }
+}
@@ -0,0 +1,188 @@
+// The MIT License (MIT)
+// Copyright (c) 2023 IBP.network
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::abot::{HealthCheckId, MemberId, ServiceId, Severity};
+use crate::config::CONFIG;
+use crate::errors::AbotError;
+use crate::http_client;
+use crate::report::RawAlert;
+use log::{debug, warn};
+use serde::Serialize;
+use std::time;
+
+const WEBHOOK_RETRY_ATTEMPTS: u32 = 3;
+const WEBHOOK_RETRY_DELAY_MS: u64 = 1000;
+
+// AlertPayload is the alert posted to the outbound webhook, in the same format the monitor sends it
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AlertPayload<'a> {
+    code: u32,
+    severity: &'a Severity,
+    message: &'a str,
+    member_id: &'a MemberId,
+    service_id: &'a ServiceId,
+    health_check_id: HealthCheckId,
+    health_checks: serde_json::Value,
+}
+
+impl<'a> From<&'a RawAlert> for AlertPayload<'a> {
+    fn from(alert: &'a RawAlert) -> Self {
+        AlertPayload {
+            code: alert.code,
+            severity: &alert.severity,
+            message: &alert.message,
+            member_id: &alert.member_id,
+            service_id: &alert.service_id,
+            health_check_id: alert.health_check_id,
+            health_checks: serde_json::from_str(&alert.data).unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Webhook {
+    client: reqwest::Client,
+    url: String,
+    retry_delay: time::Duration,
+}
+
+impl Webhook {
+    pub fn new(url: &str) -> Webhook {
+        Webhook {
+            client: http_client::build(CONFIG.http_timeout_secs),
+            url: url.to_string(),
+            retry_delay: time::Duration::from_millis(WEBHOOK_RETRY_DELAY_MS),
+        }
+    }
+
+    /// Posts the alert to the outbound webhook, retrying with backoff on failure
+    pub async fn send_alert(&self, alert: &RawAlert) -> Result<(), AbotError> {
+        let payload = AlertPayload::from(alert);
+        let mut attempt = 1;
+        loop {
+            let error = match self.client.post(&self.url).json(&payload).send().await {
+                Ok(res) if res.status().is_success() => {
+                    debug!("Alert {} posted to outbound webhook", alert.code);
+                    return Ok(());
+                }
+                Ok(res) => format!("status {}", res.status()),
+                Err(e) => e.to_string(),
+            };
+            if attempt >= WEBHOOK_RETRY_ATTEMPTS {
+                return Err(AbotError::WebhookError(format!(
+                    "alert {} not posted to {} after {} attempts: {}",
+                    alert.code, self.url, attempt, error
+                )));
+            }
+            let backoff = self.retry_delay * 2_u32.pow(attempt - 1);
+            warn!(
+                "Outbound webhook failed ({}), retry {}/{} in {:?}",
+                error,
+                attempt,
+                WEBHOOK_RETRY_ATTEMPTS - 1,
+                backoff
+            );
+            async_std::task::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn raw_alert() -> RawAlert {
+        RawAlert {
+            code: 100,
+            severity: Severity::High,
+            message: "RPC service is offline".into(),
+            member_id: "turboflakes".into(),
+            service_id: "polkadot-rpc".into(),
+            health_check_id: 1,
+            data: r#"[{"status":"error"}]"#.into(),
+            escalated_from: None,
+        }
+    }
+
+    fn webhook(server: &MockServer) -> Webhook {
+        Webhook {
+            retry_delay: time::Duration::from_millis(1),
+            ..Webhook::new(&format!("{}/hooks/ibp", server.uri()))
+        }
+    }
+
+    #[actix_web::test]
+    async fn it_posts_the_alert_payload() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hooks/ibp"))
+            .and(body_json(serde_json::json!({
+                "code": 100,
+                "severity": "high",
+                "message": "RPC service is offline",
+                "memberId": "turboflakes",
+                "serviceId": "polkadot-rpc",
+                "healthCheckId": 1,
+                "healthChecks": [{ "status": "error" }]
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        assert!(webhook(&server).send_alert(&raw_alert()).await.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn it_retries_and_gives_up_on_failure() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(u64::from(WEBHOOK_RETRY_ATTEMPTS))
+            .mount(&server)
+            .await;
+
+        let result = webhook(&server).send_alert(&raw_alert()).await;
+        assert!(matches!(result, Err(AbotError::WebhookError(_))));
+    }
+
+    #[actix_web::test]
+    async fn it_succeeds_after_a_failed_attempt() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        assert!(webhook(&server).send_alert(&raw_alert()).await.is_ok());
+    }
+}